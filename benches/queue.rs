@@ -0,0 +1,60 @@
+//! Compares the binary-heap and calendar event queues on a classic "hold" workload:
+//! a fixed population of pending events where each executed event schedules one
+//! successor a pseudo-random delay later.
+//!
+//! Run with `cargo bench --bench queue`.
+
+use std::time::Instant;
+
+use dessert::{Engine, Event, QueueKind, State, Timestamp};
+
+const POPULATION: u64 = 100_000;
+const EVENTS: usize = 1_000_000;
+
+#[derive(Clone)]
+struct Hold {
+    at: Timestamp,
+    seed: u64,
+}
+
+impl Event<u64> for Hold {
+    fn time(&self) -> Timestamp {
+        self.at
+    }
+    fn execute(self, state: &mut State<u64, Hold>) {
+        *state.state_mut() += 1;
+        // Small LCG keeps the benchmark free of extra dependencies.
+        let seed = self
+            .seed
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        let delay = (seed >> 11) as f64 / (1u64 << 53) as f64;
+        state.schedule(Hold {
+            at: self.at + delay,
+            seed,
+        });
+    }
+}
+
+fn run(kind: QueueKind) -> f64 {
+    let mut engine = Engine::builder(0).record_history(false).queue(kind).build();
+    for seed in 0..POPULATION {
+        engine.schedule(Hold {
+            at: seed as f64 / POPULATION as f64,
+            seed,
+        });
+    }
+    let started = Instant::now();
+    assert_eq!(engine.run_n_events(EVENTS), EVENTS);
+    started.elapsed().as_secs_f64()
+}
+
+fn main() {
+    for kind in [QueueKind::BinaryHeap, QueueKind::Calendar] {
+        let seconds = run(kind);
+        println!(
+            "{kind:?}: {EVENTS} events in {seconds:.3}s ({:.0} ns/event)",
+            seconds * 1e9 / EVENTS as f64
+        );
+    }
+}
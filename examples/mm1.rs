@@ -0,0 +1,120 @@
+//! The M/M/1 queue: Poisson arrivals at rate λ, one server with exponential service
+//! times at rate μ. Steady-state theory gives utilization ρ = λ/μ and mean wait in line
+//! Wq = ρ / (μ - λ), which the simulated estimates are checked against.
+//!
+//! Run with `cargo run --example mm1 --features rand`.
+
+use dessert::{Engine, Event, ResourceId, State, Timestamp};
+
+const ARRIVAL_RATE: f64 = 0.8;
+const SERVICE_RATE: f64 = 1.0;
+const WARMUP: Timestamp = 1_000.0;
+const HORIZON: Timestamp = 200_000.0;
+
+#[derive(Clone, Debug)]
+struct Shop {
+    /// Registered right after the engine is created.
+    server: Option<ResourceId>,
+    /// Whether the server is working, integrated over time for the utilization.
+    busy: bool,
+}
+
+#[derive(Debug)]
+enum Customer {
+    Arrive {
+        at: Timestamp,
+    },
+    /// Granted the server; `arrived` is when the customer joined the line.
+    Start {
+        arrived: Timestamp,
+    },
+    Depart {
+        at: Timestamp,
+    },
+}
+
+impl Event<Shop> for Customer {
+    fn time(&self) -> Timestamp {
+        match *self {
+            Customer::Arrive { at } | Customer::Depart { at } => at,
+            // Scheduled by the resource at the moment the server is granted.
+            Customer::Start { arrived } => arrived,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Customer::Arrive { .. } => "arrive",
+            Customer::Start { .. } => "start",
+            Customer::Depart { .. } => "depart",
+        }
+    }
+
+    fn execute(self, st: &mut State<Shop, Customer>) {
+        let now = st.now();
+        match self {
+            Customer::Arrive { .. } => {
+                st.schedule_exponential(ARRIVAL_RATE, |at| Customer::Arrive { at });
+                let server = st.state().server.expect("server registered");
+                st.acquire(server, Customer::Start { arrived: now });
+            }
+            Customer::Start { arrived } => {
+                st.state_mut().busy = true;
+                // Only customers who arrived after the warm-up count towards the mean.
+                if arrived >= WARMUP {
+                    st.metrics_mut().add("wait", now - arrived);
+                    st.metrics_mut().add("served", 1.0);
+                }
+                st.schedule_exponential(SERVICE_RATE, |at| Customer::Depart { at });
+            }
+            Customer::Depart { .. } => {
+                // Handing the server to the next customer sets `busy` again at once.
+                st.state_mut().busy = false;
+                let server = st.state().server.expect("server registered");
+                st.release(server);
+            }
+        }
+    }
+}
+
+fn main() {
+    let mut engine = Engine::<Shop, Customer>::new_seeded(
+        Shop {
+            server: None,
+            busy: false,
+        },
+        42,
+    )
+    .with_history_capacity(0);
+    let server = engine.add_resource(1);
+    engine.state_mut().server = Some(server);
+    engine.set_warmup(WARMUP);
+    engine.track("busy", |shop| if shop.busy { 1.0 } else { 0.0 });
+    engine.schedule(Customer::Arrive { at: 0.0 });
+    engine.run_until(HORIZON);
+
+    let rho = ARRIVAL_RATE / SERVICE_RATE;
+    let expected_wait = rho / (SERVICE_RATE - ARRIVAL_RATE);
+    let utilization = engine.time_average("busy").expect("tracked");
+    let metrics = engine.metrics();
+    let served = metrics.get("served").unwrap_or(0.0);
+    let mean_wait = metrics.get("wait").unwrap_or(0.0) / served;
+
+    println!("M/M/1 with λ={ARRIVAL_RATE}, μ={SERVICE_RATE}, {served} customers served");
+    println!("utilization:    {utilization:.4} (theory {rho:.4})");
+    println!("mean wait (Wq): {mean_wait:.4} (theory {expected_wait:.4})");
+    println!(
+        "mean events pending: {:.2}, arrivals: {}",
+        engine.mean_queue_length(),
+        engine.event_counts().get("arrive").copied().unwrap_or(0)
+    );
+
+    assert!(
+        (utilization - rho).abs() < 0.02,
+        "utilization {utilization} strays from {rho}"
+    );
+    assert!(
+        (mean_wait - expected_wait).abs() < 0.15 * expected_wait,
+        "mean wait {mean_wait} strays from {expected_wait}"
+    );
+}
@@ -171,7 +171,7 @@ fn handle_farm_event(state: &mut State<SimState, PipelineEvent>, ev: FarmEvent)
             let add = state.state().load_size_wheat;
             state.state_mut().wheat = state.state().wheat + add;
             try_start_mill_jobs(state);
-            let next_remaining = if remaining > 1 { remaining - 1 } else { 0 };
+            let next_remaining = remaining.saturating_sub(1);
             state.schedule(PipelineEvent::Farm(FarmEvent::WalkEmptyToFarm {
                 at,
                 remaining: next_remaining,
@@ -409,25 +409,6 @@ fn parse_arg_str(name: &str) -> Option<String> {
     None
 }
 
-fn write_history_csv<P: AsRef<Path>>(
-    path: P,
-    history: &[State<SimState, PipelineEvent>],
-) -> std::io::Result<()> {
-    let mut f = File::create(path)?;
-    writeln!(f, "months,wheat,flour,bread")?;
-    for st in history {
-        writeln!(
-            f,
-            "{:.6},{},{},{}",
-            st.now(),
-            st.state().wheat,
-            st.state().flour,
-            st.state().bread
-        )?;
-    }
-    Ok(())
-}
-
 fn main() {
     let farms: usize = parse_arg("--farms", 2usize);
     let mills: usize = parse_arg("--mills", 1usize);
@@ -486,13 +467,14 @@ fn main() {
         bakery_job_time,
         bakery_output_bread,
     });
+    engine.set_event_log(true);
 
-    for _ in 0..farms {
-        engine.schedule(PipelineEvent::Farm(FarmEvent::WalkEmptyToFarm {
+    engine.schedule_all((0..farms).map(|_| {
+        PipelineEvent::Farm(FarmEvent::WalkEmptyToFarm {
             at: 0.0,
             remaining: 0,
-        }));
-    }
+        })
+    }));
 
     println!(
         "Pipeline simulation: farms={}, mills={}, bakeries={}, months={}",
@@ -502,7 +484,16 @@ fn main() {
     let s = engine.state();
     println!("End: wheat={} flour={} bread={}", s.wheat, s.flour, s.bread);
 
-    if let Err(e) = write_history_csv(&csv_file, engine.history()) {
+    let history = File::create(&csv_file).and_then(|f| {
+        engine.write_history_csv(f, |st| {
+            vec![
+                ("wheat", st.wheat.into()),
+                ("flour", st.flour.into()),
+                ("bread", st.bread.into()),
+            ]
+        })
+    });
+    if let Err(e) = history {
         eprintln!("Failed to write CSV '{}': {}", csv_file, e);
     } else {
         println!("Saved CSV to {}", csv_file);
@@ -43,8 +43,8 @@ def read_events(csv_path):
 
 
 def main():
-    ap = argparse.ArgumentParser(description="Plot Stronghold DES CSV (months,wheat,flour,bread) with optional event overlay")
-    ap.add_argument("csv", help="Input history CSV (months,wheat,flour,bread)")
+    ap = argparse.ArgumentParser(description="Plot Stronghold DES CSV (time,wheat,flour,bread) with optional event overlay")
+    ap.add_argument("csv", help="Input history CSV (time,wheat,flour,bread)")
     ap.add_argument("out", nargs="?", default=None, help="Optional output PNG path; if omitted and --show is set, just shows window")
     ap.add_argument("--show", action="store_true", help="Show an interactive window")
     ap.add_argument("--events", default=None, help="Optional events CSV (months,event) to overlay")
@@ -54,13 +54,13 @@ def main():
     months, wheat, flour, bread = [], [], [], []
     with open(args.csv, newline="") as f:
         r = csv.DictReader(f)
-        required = {"months", "wheat", "flour", "bread"}
+        required = {"time", "wheat", "flour", "bread"}
         if not required.issubset(r.fieldnames or {}):
-            print("CSV missing required headers months,wheat,flour,bread", file=sys.stderr)
+            print("CSV missing required headers time,wheat,flour,bread", file=sys.stderr)
             sys.exit(2)
         for row in r:
             try:
-                t = float(row["months"]) ; w = int(row["wheat"]) ; fl = int(row["flour"]) ; br = int(row["bread"]) 
+                t = float(row["time"]) ; w = int(row["wheat"]) ; fl = int(row["flour"]) ; br = int(row["bread"]) 
             except Exception:
                 continue
             months.append(t) ; wheat.append(w) ; flour.append(fl) ; bread.append(br)
@@ -0,0 +1,213 @@
+//! Checkpoint and resume (requires the `serde` feature).
+//!
+//! `Engine::save` captures the clock, user data, metrics, pending queue and resources in
+//! a `SerializedEngine`, which can be written with any serde data format and handed to
+//! `Engine::restore` later, possibly in another process. Queued events keep their time
+//! and scheduling sequence number, so ties resolve exactly as they would have without
+//! the interruption.
+//!
+//! Engine configuration (history, logs, policies, hooks, coalescing) and the executed
+//! event logs are not part of a checkpoint; configure the restored engine again. Guards
+//! (`State::schedule_if`) and periodic series (`State::schedule_periodic`) are closures
+//! and cannot be saved, so their pending events are restored as plain one-off events.
+
+use std::collections::BTreeSet;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "rand")]
+use rand::{Rng, SeedableRng};
+
+use crate::queue::EventQueue;
+use crate::{Engine, Event, EventHandle, Metrics, Resource, Scheduled, SimTime, Timestamp};
+
+/// A saved engine, produced by `Engine::save`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SerializedEngine<S, E, T = Timestamp> {
+    now: T,
+    data: S,
+    metrics: Vec<(String, f64)>,
+    queue: Vec<QueuedEvent<E, T>>,
+    next_seq: u64,
+    cancelled: Vec<u64>,
+    resources: Vec<Resource<E>>,
+    queue_area: f64,
+    stats_start: T,
+    event_counts: Vec<(String, u64)>,
+    warmup: Option<T>,
+    /// Seed for the restored generator, drawn from the saved one.
+    #[cfg(feature = "rand")]
+    rng_seed: u64,
+}
+
+/// A pending event with the key that orders it in the queue.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct QueuedEvent<E, T> {
+    at: T,
+    rank: i64,
+    seq: u64,
+    parent: Option<u64>,
+    event: E,
+}
+
+impl<S: Clone, E: Event<S, T> + Clone, T: SimTime> Engine<S, E, T> {
+    /// Capture everything needed to continue this simulation later.
+    ///
+    /// With the `rand` feature the generator itself cannot be serialized; the checkpoint
+    /// stores a seed drawn from it instead, so a resumed run is reproducible but its
+    /// random draws differ from those of an uninterrupted run.
+    pub fn save(&self) -> SerializedEngine<S, E, T> {
+        let state = &self.state;
+        let mut queue: Vec<_> = state
+            .queue
+            .iter()
+            .map(|scheduled| QueuedEvent {
+                at: scheduled.at,
+                rank: scheduled.rank,
+                seq: scheduled.seq,
+                parent: scheduled.parent.map(|handle| handle.0),
+                event: scheduled.event.clone(),
+            })
+            .collect();
+        queue.sort_by_key(|queued| queued.seq);
+        SerializedEngine {
+            now: state.now,
+            data: state.data.clone(),
+            metrics: state
+                .metrics
+                .iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            queue,
+            next_seq: state.next_seq,
+            cancelled: state.cancelled.iter().map(|handle| handle.0).collect(),
+            resources: state.resources.clone(),
+            queue_area: state.queue_area,
+            stats_start: state.stats_start,
+            event_counts: state
+                .event_counts
+                .iter()
+                .map(|(label, count)| (label.to_string(), *count))
+                .collect(),
+            warmup: state.warmup,
+            #[cfg(feature = "rand")]
+            rng_seed: state.rng.clone().random(),
+        }
+    }
+
+    /// Build an engine that continues from a checkpoint taken with `save`.
+    ///
+    /// History starts over with a snapshot of the restored state. Metric names and event
+    /// labels are `&'static str`, so each distinct name is leaked once per process.
+    pub fn restore(saved: SerializedEngine<S, E, T>) -> Self {
+        let mut engine = Self::new_at(saved.data, saved.now);
+        let state = &mut engine.state;
+        let mut metrics = Metrics::default();
+        for (name, value) in saved.metrics {
+            metrics.set(intern(name), value);
+        }
+        state.metrics = metrics;
+        for queued in saved.queue {
+            state.queue.push(Scheduled::new(
+                queued.at,
+                queued.rank,
+                queued.seq,
+                queued.parent.map(EventHandle),
+                queued.event,
+            ));
+        }
+        state.next_seq = saved.next_seq;
+        state.cancelled = saved.cancelled.into_iter().map(EventHandle).collect();
+        state.resources = saved.resources;
+        state.queue_area = saved.queue_area;
+        state.stats_start = saved.stats_start;
+        state.event_counts = saved
+            .event_counts
+            .into_iter()
+            .map(|(label, count)| (intern(label), count))
+            .collect();
+        state.warmup = saved.warmup;
+        #[cfg(feature = "rand")]
+        {
+            state.rng = rand::rngs::StdRng::seed_from_u64(saved.rng_seed);
+        }
+        engine.warmup = saved.warmup;
+        engine.history.clear();
+        engine.history_points.clear();
+        engine.record_snapshot();
+        engine
+    }
+}
+
+/// Turn a deserialized name back into a `&'static str`, leaking each distinct name once.
+fn intern(name: String) -> &'static str {
+    static NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+    let mut names = NAMES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(&interned) = names.get(name.as_str()) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.into_boxed_str());
+    names.insert(interned);
+    interned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::State;
+
+    #[derive(Clone, Debug, Serialize, Deserialize)]
+    struct Split {
+        at: Timestamp,
+        id: u32,
+    }
+
+    impl Event<Vec<(Timestamp, u32)>> for Split {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<Vec<(Timestamp, u32)>, Split>) {
+            state.state_mut().push((self.at, self.id));
+            state.metrics_mut().add("splits", 1.0);
+            if self.id < 15 {
+                // Children share a time, so only `seq` fixes the order they run in.
+                for child in [2 * self.id + 2, 2 * self.id + 1] {
+                    state.schedule(Split {
+                        at: self.at + 1.0,
+                        id: child,
+                    });
+                }
+            }
+        }
+    }
+
+    fn seeded() -> Engine<Vec<(Timestamp, u32)>, Split> {
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule(Split { at: 0.0, id: 0 });
+        engine
+    }
+
+    #[test]
+    fn restored_engine_matches_an_uninterrupted_run() {
+        let mut uninterrupted = seeded();
+        uninterrupted.run_until(10.0);
+
+        let mut first = seeded();
+        for _ in 0..5 {
+            first.step();
+        }
+        let json = serde_json::to_string(&first.save()).unwrap();
+        let mut resumed: Engine<_, Split> = Engine::restore(serde_json::from_str(&json).unwrap());
+        resumed.run_until(10.0);
+
+        assert_eq!(resumed.state(), uninterrupted.state());
+        assert_eq!(resumed.now(), uninterrupted.now());
+        assert_eq!(
+            resumed.metrics().get("splits"),
+            uninterrupted.metrics().get("splits")
+        );
+    }
+}
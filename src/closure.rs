@@ -0,0 +1,98 @@
+//! Ad-hoc events built from closures.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::{Event, State, Timestamp};
+
+/// Event type for one-off closures: a `(time, action)` pair.
+///
+/// A closure scheduled on an engine must accept the state handle, whose type names the
+/// event type, which in turn names the closure. `Action` is the named type that breaks
+/// this cycle, and this alias is what goes in `Engine<S, ClosureEvent<S>>`.
+///
+/// ```
+/// use dessert::{Action, ClosureEvent, Engine, State};
+///
+/// let mut engine = Engine::<u32, ClosureEvent<u32>>::new(0);
+/// engine.schedule((5.0, Action::new(|st: &mut State<u32, ClosureEvent<u32>>| {
+///     *st.state_mut() += 1;
+///     let later = st.now() + 1.0;
+///     st.schedule((later, Action::new(|st: &mut State<u32, ClosureEvent<u32>>| {
+///         *st.state_mut() *= 10;
+///     })));
+/// })));
+/// engine.run_until(10.0);
+/// assert_eq!(*engine.state(), 10);
+/// ```
+pub type ClosureEvent<S> = (Timestamp, Action<S>);
+
+/// The body of a `ClosureEvent`.
+///
+/// Stored as a shared `Fn` so that the event can be cloned: restore points (see
+/// `Engine::set_rewindable`) and `Engine::dry_run_count` copy the pending queue, and
+/// each copy runs the closure again. `Action::once` accepts a `FnOnce` instead, at the
+/// cost of running it at most once across all copies.
+pub struct Action<S>(Rc<ActionFn<S>>);
+
+/// The closure type wrapped by `Action`.
+type ActionFn<S> = dyn Fn(&mut State<S, ClosureEvent<S>>);
+
+impl<S> Action<S> {
+    /// Wrap a closure to run when the event fires.
+    pub fn new(f: impl Fn(&mut State<S, ClosureEvent<S>>) + 'static) -> Self {
+        Self(Rc::new(f))
+    }
+
+    /// Wrap a closure that may consume what it captures, e.g. to move a value into the
+    /// state.
+    ///
+    /// Clones of the action share the closure, so only the first copy to fire runs it
+    /// and later ones do nothing. In particular `Engine::dry_run_count` would use it up,
+    /// and replaying after `Engine::rewind` does not run it again.
+    ///
+    /// ```
+    /// use dessert::{Action, ClosureEvent, Engine, State};
+    ///
+    /// type Names = Vec<String>;
+    ///
+    /// let mut engine = Engine::<Names, ClosureEvent<Names>>::new(Vec::new());
+    /// let name = String::from("mill");
+    /// engine.schedule((1.0, Action::once(move |st: &mut State<Names, ClosureEvent<Names>>| {
+    ///     st.state_mut().push(name);
+    /// })));
+    /// engine.run_until(2.0);
+    /// assert_eq!(engine.state(), &["mill"]);
+    /// ```
+    pub fn once(f: impl FnOnce(&mut State<S, ClosureEvent<S>>) + 'static) -> Self {
+        let f = RefCell::new(Some(f));
+        Self::new(move |state| {
+            if let Some(f) = f.borrow_mut().take() {
+                f(state);
+            }
+        })
+    }
+}
+
+impl<S> Clone for Action<S> {
+    fn clone(&self) -> Self {
+        Self(Rc::clone(&self.0))
+    }
+}
+
+impl<S> fmt::Debug for Action<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Action")
+    }
+}
+
+impl<S> Event<S> for (Timestamp, Action<S>) {
+    fn time(&self) -> Timestamp {
+        self.0
+    }
+
+    fn execute(self, state: &mut State<S, Self>) {
+        (self.1 .0)(state)
+    }
+}
@@ -0,0 +1,170 @@
+//! Heterogeneous events behind trait objects.
+
+use crate::{Engine, Event, SimTime, State, Timestamp};
+
+/// Object-safe counterpart of `Event`, for models whose event types are defined
+/// independently and scheduled together without a central enum.
+///
+/// `Event::execute` takes `self` by value and names the concrete event type in its state
+/// handle, so `dyn Event` cannot exist. Here the event is consumed through
+/// `self: Box<Self>` and the state handle is the one shared by every boxed event,
+/// `DynState<S, T>`. `Box<dyn DynEvent<S, T>>` implements `Event`, so a `DynEngine`
+/// is an ordinary `Engine`.
+///
+/// ```
+/// use dessert::{DynEngine, DynEvent, DynState};
+///
+/// struct Ping(f64);
+///
+/// impl DynEvent<u32> for Ping {
+///     fn time(&self) -> f64 {
+///         self.0
+///     }
+///     fn execute(self: Box<Self>, state: &mut DynState<u32>) {
+///         *state.state_mut() += 1;
+///     }
+/// }
+///
+/// let mut engine = DynEngine::new(0);
+/// engine.schedule(Box::new(Ping(1.0)));
+/// engine.run_until(5.0);
+/// assert_eq!(*engine.state(), 1);
+/// ```
+pub trait DynEvent<S, T: SimTime = Timestamp> {
+    /// Time at which this event should fire (see `Event::time`).
+    fn time(&self) -> T;
+
+    /// Short, static name of this kind of event (see `Event::label`).
+    fn label(&self) -> &'static str {
+        "event"
+    }
+
+    /// Tie-break among events at the same time (see `Event::priority`).
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Phase of this event within an instant (see `Event::phase`).
+    fn phase(&self) -> u8 {
+        0
+    }
+
+    /// Execute the event logic (see `Event::execute`).
+    fn execute(self: Box<Self>, state: &mut DynState<S, T>);
+
+    /// Clean up after this event was cancelled (see `Event::on_cancel`).
+    fn on_cancel(self: Box<Self>, _state: &mut DynState<S, T>) {}
+}
+
+/// A boxed event of any type implementing `DynEvent`.
+pub type BoxedEvent<S, T = Timestamp> = Box<dyn DynEvent<S, T>>;
+
+/// State handle seen by boxed events.
+pub type DynState<S, T = Timestamp> = State<S, BoxedEvent<S, T>, T>;
+
+/// An engine whose queue holds boxed events of mixed types.
+pub type DynEngine<S, T = Timestamp> = Engine<S, BoxedEvent<S, T>, T>;
+
+impl<S, T: SimTime> Event<S, T> for BoxedEvent<S, T> {
+    fn time(&self) -> T {
+        (**self).time()
+    }
+
+    fn label(&self) -> &'static str {
+        (**self).label()
+    }
+
+    fn priority(&self) -> i32 {
+        (**self).priority()
+    }
+
+    fn phase(&self) -> u8 {
+        (**self).phase()
+    }
+
+    fn execute(self, state: &mut DynState<S, T>) {
+        DynEvent::execute(self, state);
+    }
+
+    fn on_cancel(self, state: &mut DynState<S, T>) {
+        DynEvent::on_cancel(self, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct Farm {
+        harvested: u32,
+        milled: u32,
+        log: Vec<(Timestamp, &'static str)>,
+    }
+
+    // Two subsystems, each with its own event type; neither knows about an enum.
+    struct Harvest {
+        at: Timestamp,
+    }
+
+    struct Mill {
+        at: Timestamp,
+        sacks: u32,
+    }
+
+    impl DynEvent<Farm> for Harvest {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn label(&self) -> &'static str {
+            "harvest"
+        }
+        fn execute(self: Box<Self>, state: &mut DynState<Farm>) {
+            let farm = state.state_mut();
+            farm.harvested += 1;
+            farm.log.push((self.at, "harvest"));
+            state.schedule(Box::new(Mill {
+                at: self.at + 0.5,
+                sacks: 3,
+            }));
+        }
+    }
+
+    impl DynEvent<Farm> for Mill {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn label(&self) -> &'static str {
+            "mill"
+        }
+        fn execute(self: Box<Self>, state: &mut DynState<Farm>) {
+            let farm = state.state_mut();
+            farm.milled += self.sacks;
+            farm.log.push((self.at, "mill"));
+        }
+    }
+
+    #[test]
+    fn boxed_events_of_different_types_share_one_queue() {
+        let mut engine = DynEngine::new(Farm::default());
+        engine.schedule(Box::new(Harvest { at: 1.0 }));
+        engine.schedule(Box::new(Mill { at: 0.0, sacks: 1 }));
+        engine.schedule(Box::new(Harvest { at: 2.0 }));
+        engine.run_until(10.0);
+
+        let farm = engine.state();
+        assert_eq!(farm.harvested, 2);
+        assert_eq!(farm.milled, 7);
+        assert_eq!(
+            farm.log,
+            vec![
+                (0.0, "mill"),
+                (1.0, "harvest"),
+                (1.5, "mill"),
+                (2.0, "harvest"),
+                (2.5, "mill"),
+            ]
+        );
+        assert_eq!(engine.event_counts().get("mill"), Some(&3));
+    }
+}
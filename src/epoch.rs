@@ -0,0 +1,67 @@
+//! Wall-clock dates for simulated time (requires the `chrono` feature).
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{Engine, Event, SimTime};
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// Anchor simulated time to the calendar: time 0 is `start` and each time unit
+    /// lasts `unit`, so reports can show dates instead of raw timestamps. Requires the
+    /// `chrono` feature.
+    pub fn with_epoch(mut self, start: DateTime<Utc>, unit: TimeDelta) -> Self {
+        self.epoch = Some((start, unit));
+        self
+    }
+
+    /// The date the clock currently stands at (see `to_wall_time`).
+    pub fn wall_time(&self) -> Option<DateTime<Utc>> {
+        self.to_wall_time(self.now())
+    }
+
+    /// The date of simulated time `t`, `start + t * unit` with the epoch set by
+    /// `with_epoch`, to the nanosecond. `None` without an epoch or if the date is out
+    /// of range, e.g. for an infinite `t`.
+    pub fn to_wall_time(&self, t: T) -> Option<DateTime<Utc>> {
+        let (start, unit) = self.epoch?;
+        let nanos = unit.num_nanoseconds()? as f64 * t.to_f64();
+        if !nanos.is_finite() || nanos.abs() >= i64::MAX as f64 {
+            return None;
+        }
+        start.checked_add_signed(TimeDelta::nanoseconds(nanos.round() as i64))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone};
+
+    use super::*;
+    use crate::{State, Timestamp};
+
+    struct Invoice(Timestamp);
+
+    impl Event<u32> for Invoice {
+        fn time(&self) -> Timestamp {
+            self.0
+        }
+        fn execute(self, state: &mut State<u32, Invoice>) {
+            *state.state_mut() += 1;
+        }
+    }
+
+    #[test]
+    fn simulated_months_map_to_dates() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        // The mean Gregorian month, 365.2425 / 12 days.
+        let month = TimeDelta::seconds(2_629_746);
+        let mut engine = Engine::new(0).with_epoch(start, month);
+        assert_eq!(engine.wall_time(), Some(start));
+
+        engine.schedule(Invoice(3.0));
+        engine.run_until(3.0);
+        let date = engine.wall_time().unwrap().date_naive();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2024, 4, 1).unwrap());
+        assert_eq!(engine.to_wall_time(f64::INFINITY), None);
+        assert_eq!(Engine::<u32, Invoice>::new(0).wall_time(), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Events whose logic can fail without panicking.
+
+use std::fmt;
+
+use crate::{Engine, Event, ScheduleError, SimTime, State, Timestamp};
+
+/// An error returned by a `FallibleEvent`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SimError {
+    message: String,
+}
+
+impl SimError {
+    /// An error described by `message`.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    /// The description given to `new`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SimError {}
+
+/// Lets `State::try_schedule` failures propagate with `?`.
+impl From<ScheduleError> for SimError {
+    fn from(err: ScheduleError) -> Self {
+        Self::new(err.to_string())
+    }
+}
+
+/// What a run does when an event fails (see `Engine::set_error_policy`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Record the error and carry on with the next event.
+    #[default]
+    Continue,
+    /// Record the error and stop the run with `StopReason::EventFailed`, leaving the
+    /// clock at the failed event.
+    Abort,
+}
+
+/// An event whose logic may fail, reporting a `SimError` instead of panicking.
+///
+/// Wrap such events in `Fallible` to schedule them; the engine collects the errors in
+/// `Engine::errors` and applies its `ErrorPolicy`. Whatever the failed event changed
+/// or scheduled before returning the error stays in effect.
+pub trait FallibleEvent<S, T: SimTime = Timestamp>: Sized {
+    /// Time at which this event should fire (see `Event::time`).
+    fn time(&self) -> T;
+
+    /// Short, static name of this kind of event (see `Event::label`).
+    fn label(&self) -> &'static str {
+        "event"
+    }
+
+    /// Tie-break among events at the same time (see `Event::priority`).
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Phase of this event within an instant (see `Event::phase`).
+    fn phase(&self) -> u8 {
+        0
+    }
+
+    /// Execute the event logic (see `Event::execute`), or report why it failed.
+    fn try_execute(self, state: &mut State<S, Fallible<Self>, T>) -> Result<(), SimError>;
+
+    /// Clean up after this event was cancelled (see `Event::on_cancel`).
+    fn on_cancel(self, _state: &mut State<S, Fallible<Self>, T>) {}
+}
+
+/// Adapter running a `FallibleEvent` as an `Event`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fallible<E>(pub E);
+
+impl<S, E: FallibleEvent<S, T>, T: SimTime> Event<S, T> for Fallible<E> {
+    fn time(&self) -> T {
+        self.0.time()
+    }
+
+    fn label(&self) -> &'static str {
+        self.0.label()
+    }
+
+    fn priority(&self) -> i32 {
+        self.0.priority()
+    }
+
+    fn phase(&self) -> u8 {
+        self.0.phase()
+    }
+
+    fn execute(self, state: &mut State<S, Self, T>) {
+        if let Err(err) = self.0.try_execute(state) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(at = ?state.now, error = %err, "event failed");
+            state.errors.push((state.now, err));
+        }
+    }
+
+    fn on_cancel(self, state: &mut State<S, Self, T>) {
+        self.0.on_cancel(state);
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// Choose whether a run stops at the first failed event. Defaults to
+    /// `ErrorPolicy::Continue`.
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+
+    /// Errors returned by failed events so far, with the times they failed at.
+    pub fn errors(&self) -> &[(T, SimError)] {
+        &self.state.errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StopReason;
+
+    #[derive(Clone, Debug, Default)]
+    struct Silo {
+        grain: u32,
+        shipped: u32,
+    }
+
+    enum Order {
+        Fill { at: Timestamp, tons: u32 },
+        Ship { at: Timestamp, tons: u32 },
+    }
+
+    impl FallibleEvent<Silo> for Order {
+        fn time(&self) -> Timestamp {
+            match *self {
+                Order::Fill { at, .. } | Order::Ship { at, .. } => at,
+            }
+        }
+        fn try_execute(self, state: &mut State<Silo, Fallible<Order>>) -> Result<(), SimError> {
+            let silo = state.state_mut();
+            match self {
+                Order::Fill { tons, .. } => silo.grain += tons,
+                Order::Ship { tons, .. } => {
+                    silo.grain = silo.grain.checked_sub(tons).ok_or_else(|| {
+                        SimError::new(format!("cannot ship {tons} t from {} t", silo.grain))
+                    })?;
+                    silo.shipped += tons;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn orders() -> Engine<Silo, Fallible<Order>> {
+        let mut engine = Engine::new(Silo::default());
+        engine.schedule_all([
+            Fallible(Order::Fill { at: 1.0, tons: 5 }),
+            Fallible(Order::Ship { at: 2.0, tons: 8 }),
+            Fallible(Order::Fill { at: 3.0, tons: 5 }),
+            Fallible(Order::Ship { at: 4.0, tons: 8 }),
+        ]);
+        engine
+    }
+
+    #[test]
+    fn failed_events_are_recorded_and_the_run_continues() {
+        let mut engine = orders();
+        let outcome = engine.run_until(10.0);
+        assert_eq!(outcome.reason, StopReason::QueueEmpty);
+        assert_eq!(outcome.events_executed, 4);
+        assert_eq!(engine.state().shipped, 8);
+        assert_eq!(engine.state().grain, 2);
+        assert_eq!(
+            engine.errors(),
+            &[(2.0, SimError::new("cannot ship 8 t from 5 t"))]
+        );
+    }
+
+    #[test]
+    fn abort_policy_stops_at_the_failed_event() {
+        let mut engine = orders();
+        engine.set_error_policy(ErrorPolicy::Abort);
+        let outcome = engine.run_until(10.0);
+        assert_eq!(outcome.reason, StopReason::EventFailed);
+        assert_eq!(outcome.stopped_at, 2.0);
+        assert_eq!(engine.errors().len(), 1);
+
+        // Resuming carries on with the events after the failure.
+        assert_eq!(engine.run_until(10.0).reason, StopReason::QueueEmpty);
+        assert_eq!(engine.state().shipped, 8);
+    }
+
+    #[test]
+    fn abort_policy_applies_to_every_runner() {
+        let mut engine = orders();
+        engine.set_error_policy(ErrorPolicy::Abort);
+        let mut frames = Vec::new();
+        let outcome = engine.run_by_time_step(10.0, |t, silo| frames.push((t, silo.grain)));
+        assert_eq!(outcome.reason, StopReason::EventFailed);
+        assert_eq!(outcome.stopped_at, 2.0);
+        assert_eq!(frames, vec![(1.0, 5)]);
+
+        let mut engine = orders();
+        engine.set_error_policy(ErrorPolicy::Abort);
+        let outcome = engine.run_with_source(10.0, std::iter::empty());
+        assert_eq!(outcome.reason, StopReason::EventFailed);
+        assert_eq!(outcome.events_executed, 2);
+
+        let mut engine = orders();
+        engine.set_error_policy(ErrorPolicy::Abort);
+        engine.run();
+        assert_eq!(engine.now(), 2.0);
+        assert_eq!(engine.pending_len(), 2);
+
+        let mut engine = orders();
+        engine.set_error_policy(ErrorPolicy::Abort);
+        assert_eq!(engine.run_n_events(4), 2);
+        assert_eq!(engine.state().grain, 5);
+    }
+}
@@ -6,8 +6,9 @@
 //! - Let events mutate simulation state and enqueue more events via a restricted `State` handle,
 //!   while a separate `Engine` drives the main loop.
 //!
-//! Non-goals (for now): resources, processes, and distributions. These can be layered
-//! on top later (e.g., a process/coroutine API that schedules future events).
+//! Bounded servers are available as `Resource`s. Non-goals (for now): processes and
+//! distributions. These can be layered on top later (e.g., a process/coroutine API that
+//! schedules future events).
 //!
 //! # Quick example
 //!
@@ -36,83 +37,486 @@
 //! engine.run_until(10.0);
 //! assert_eq!(engine.state().ticks, 5);
 //! ```
+//!
+//! # Event ordering
+//!
+//! The firing order is part of the public contract. Events fire in increasing time;
+//! events at the same time fire by increasing `Event::phase()`, then by increasing
+//! `Event::priority()` (both default 0), and events with equal time, phase and priority
+//! fire in the order they were scheduled (FIFO).
+//! `State::schedule_now_last` places an event after all of these at its instant.
+//! The order is fully determined by these keys, never by how the queue happens to lay
+//! out its entries, so identical runs execute identically whichever `QueueKind` they use.
+//!
+//! ```
+//! use dessert::{Engine, Event, State, Timestamp};
+//!
+//! #[derive(Clone, Debug)]
+//! struct Named { name: &'static str, priority: i32 }
+//!
+//! impl Event<Vec<&'static str>> for Named {
+//!     fn time(&self) -> Timestamp { 1.0 }
+//!     fn priority(&self) -> i32 { self.priority }
+//!     fn execute(self, st: &mut State<Vec<&'static str>, Named>) {
+//!         st.state_mut().push(self.name);
+//!     }
+//! }
+//!
+//! let mut engine = Engine::<Vec<&'static str>, Named>::new(Vec::new());
+//! engine.schedule(Named { name: "first", priority: 0 });
+//! engine.schedule(Named { name: "urgent", priority: -1 });
+//! engine.schedule(Named { name: "second", priority: 0 });
+//! engine.run_until(2.0);
+//! assert_eq!(engine.state(), &vec!["urgent", "first", "second"]);
+//! ```
+//!
+//! # Event log
+//!
+//! The string event log returned by `Engine::events` is opt-in: enable it with
+//! `Engine::set_event_log(true)`, which needs the events to implement `Debug`. Earlier
+//! versions recorded it for every engine, so code reading `events()` without enabling
+//! the log now sees it empty.
+
+#[cfg(feature = "serde")]
+pub mod checkpoint;
+mod closure;
+#[cfg(feature = "csv")]
+mod csv;
+mod dynamic;
+#[cfg(feature = "chrono")]
+mod epoch;
+mod fallible;
+mod metrics;
+mod pure;
+mod queue;
+mod resource;
+#[cfg(feature = "serde")]
+pub mod scenario;
+mod time;
+
+pub use closure::{Action, ClosureEvent};
+pub use dynamic::{BoxedEvent, DynEngine, DynEvent, DynState};
+pub use fallible::{ErrorPolicy, Fallible, FallibleEvent, SimError};
+pub use metrics::Metrics;
+pub use pure::{Pure, PureEvent};
+pub use queue::QueueKind;
+pub use resource::{Resource, ResourceId};
+pub use time::{Duration, SimTime};
 
+use std::any::{Any, TypeId};
+use std::cell::RefCell;
 use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Instant;
 
-/// Simulation timestamp type (continuous time supported).
+use queue::{EventQueue, Queue};
+
+#[cfg(feature = "rand")]
+use rand::{rngs::StdRng, SeedableRng};
+
+/// Default simulation timestamp type (continuous time).
+///
+/// Engines, states and events take the time type as a trailing parameter that defaults
+/// to this; see `SimTime` for other choices such as integer ticks.
 pub type Timestamp = f64;
 
+/// Sampler for the delay added to schedule times (see `Engine::set_schedule_jitter`).
+#[cfg(feature = "rand")]
+type JitterFn<T> = Rc<dyn Fn(&mut StdRng) -> T>;
+
+/// Observer of accepted schedule calls (see `Engine::on_schedule`).
+type ScheduleHook<E, T> = Rc<RefCell<dyn FnMut(T, &E)>>;
+
+/// Precondition re-checked when a guarded event comes due (see `State::schedule_if`).
+type Guard<S> = Rc<dyn Fn(&S) -> bool>;
+
+/// A recurring event created by `State::schedule_periodic`.
+struct Series<E, T> {
+    /// Handle of the first occurrence, which identifies the whole series.
+    id: EventHandle,
+    period: T,
+    make: Box<dyn Fn(T) -> E>,
+}
+
+/// Observer called around each executed event (see `Engine::on_before`).
+type ExecuteHook<S, T> = Box<dyn FnMut(T, &S)>;
+/// Callback for a time marker (see `Engine::add_time_marker`).
+type MarkerFn<S> = Box<dyn FnMut(&S)>;
+type RestoreFn<S, E, T, C> = fn(&State<S, E, T, C>) -> State<S, E, T, C>;
+
 /// Trait for events that mutate `State` and may schedule more events.
 ///
 /// Implementors should be plain data types carrying the scheduled time and any payload
 /// needed to execute. The engine calls `execute` when the event reaches the head of the
 /// queue and the simulation time advances to its timestamp.
-pub trait Event<S>: Sized {
+///
+/// `C` is the type of the read-only context of engines built with
+/// `Engine::with_context`, which `execute` reads through `State::ctx`; it defaults to
+/// `()` for engines without one.
+pub trait Event<S, T: SimTime = Timestamp, C = ()>: Sized {
     /// Time at which this event should fire.
-    fn time(&self) -> Timestamp;
+    fn time(&self) -> T;
+
+    /// Short, static name of this kind of event, used to group events in reports.
+    /// Defaults to `"event"`; enums typically return one name per variant.
+    fn label(&self) -> &'static str {
+        "event"
+    }
+
+    /// Tie-break among events at the same time and phase: lower values fire first.
+    /// Events with equal time, phase and priority fire in scheduling order. Defaults to 0.
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Phase of this event within an instant: all phase-0 events at a time fire
+    /// before any phase-1 event at that time, and so on, whatever their priorities.
+    /// `priority` orders events within a phase. Defaults to 0.
+    ///
+    /// Useful for models that first apply every state update at an instant and then
+    /// observe the result.
+    fn phase(&self) -> u8 {
+        0
+    }
 
     /// Execute the event logic, mutating state and optionally scheduling more events
     /// via the provided state handle. Consumes the event (one-shot).
-    fn execute(self, state: &mut State<S, Self>);
+    ///
+    /// The engine advances the clock before calling `execute`, so inside it
+    /// `state.now()` is exactly the time this event was scheduled for: `self.time()`,
+    /// unless the engine was configured to perturb schedule times (see
+    /// `Engine::set_schedule_jitter`). Event code may rely on this instead of carrying
+    /// its own copy of the timestamp.
+    fn execute(self, state: &mut State<S, Self, T, C>);
+
+    /// Clean up after this event was cancelled instead of executed, e.g. to return a
+    /// reserved worker, or to tell a timeout that was pre-empted from one that fired.
+    /// The default does nothing.
+    ///
+    /// Called once for an event cancelled with `State::cancel`, when its entry is
+    /// dropped from the queue, and for every event cleared by
+    /// `State::cancel_all(true)`.
+    fn on_cancel(self, _state: &mut State<S, Self, T, C>) {}
+}
+
+/// Opaque identifier of an event accepted into the queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EventHandle(u64);
+
+impl EventHandle {
+    /// The event's scheduling sequence number, as used by `Engine::causality`.
+    pub fn seq(self) -> u64 {
+        self.0
+    }
+}
+
+/// Parent recorded by `Engine::causality` for events scheduled from outside a run.
+pub const ROOT_PARENT: u64 = u64::MAX;
+
+/// Result of asking the `State` to schedule an event (see `State::schedule_result`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleOutcome {
+    /// The event entered the queue.
+    Scheduled(EventHandle),
+    /// The event was earlier than `now` and the past-event policy rejects such events.
+    RejectedPast,
+    /// The event's time was NaN or infinite.
+    RejectedNonFinite,
+    /// The event's time exceeded the engine's maximum simulated time.
+    RejectedBeyondMaxTime,
+    /// An identical event was already pending at the same time, so this one was merged
+    /// into it (see `EngineBuilder::dedup_identical`).
+    Coalesced,
+}
+
+/// Why `State::try_schedule` refused an event.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScheduleError {
+    /// The event was earlier than `now`.
+    Past,
+    /// The event's time was NaN or infinite.
+    NonFinite,
+    /// The event's time exceeded the engine's maximum simulated time.
+    BeyondMaxTime,
+}
+
+impl std::fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Past => "event scheduled before the current time",
+            Self::NonFinite => "event scheduled at a non-finite time",
+            Self::BeyondMaxTime => "event scheduled beyond the maximum simulated time",
+        })
+    }
+}
+
+impl std::error::Error for ScheduleError {}
+
+/// Why `Engine::rewind_to` could not return to a snapshot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewindError {
+    /// No snapshot has that index in `Engine::history`.
+    OutOfRange,
+    /// The snapshot was recorded without a restore point (see `Engine::set_rewindable`).
+    NotRewindable,
+}
+
+impl std::fmt::Display for RewindError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::OutOfRange => "no history snapshot at that index",
+            Self::NotRewindable => "history snapshot has no restore point",
+        })
+    }
+}
+
+impl std::error::Error for RewindError {}
+
+/// What to do with events scheduled earlier than the current simulation time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PastEventPolicy {
+    /// Accept the event; it fires next, at its (past) timestamp. Debug builds panic
+    /// when it executes, as the clock would run backwards.
+    #[default]
+    Allow,
+    /// Reject the event with `ScheduleOutcome::RejectedPast`.
+    Error,
+}
+
+/// Rank of an event in the queue: its phase in the high bits, its priority below.
+///
+/// Every phase spans a disjoint range of ranks, so phases order before priorities.
+pub fn rank_of<S, T: SimTime, E: Event<S, T, C>, C>(event: &E) -> i64 {
+    (i64::from(event.phase()) << 32) + i64::from(event.priority())
+}
+
+/// Rank of events scheduled with `State::schedule_now_last`.
+const RANK_LAST: i64 = i64::MAX;
+
+/// The key that decides the firing order of pending events.
+///
+/// Smaller keys fire first: keys compare by time (using `SimTime::cmp_time`), then by
+/// rank, then by insertion sequence, so equal-time, equal-rank events fire FIFO. This is
+/// the same rule the engine's queue uses, exposed so custom queues and tests can rely
+/// on it.
+#[derive(Clone, Copy, Debug)]
+pub struct ScheduleKey<T = Timestamp> {
+    /// Firing time.
+    pub at: T,
+    /// The event's `Event::phase()` and `Event::priority()` combined, phase first (see
+    /// `rank_of`), or `i64::MAX` for `State::schedule_now_last`.
+    pub rank: i64,
+    /// Insertion sequence number, increasing with every scheduled event.
+    pub seq: u64,
+}
+
+impl<T: SimTime> PartialEq for ScheduleKey<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl<T: SimTime> Eq for ScheduleKey<T> {}
+impl<T: SimTime> PartialOrd for ScheduleKey<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+impl<T: SimTime> Ord for ScheduleKey<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.at
+            .cmp_time(&other.at)
+            .then(self.rank.cmp(&other.rank))
+            .then(self.seq.cmp(&other.seq))
+    }
 }
 
+/// A queued event, ordered by its `ScheduleKey`.
 #[derive(Clone)]
-struct Scheduled<S, E: Event<S>> {
-    at: Timestamp,
+struct Scheduled<S, E, T: SimTime> {
+    at: T,
+    rank: i64,
+    seq: u64,
+    /// The event that was executing when this one was scheduled.
+    parent: Option<EventHandle>,
+    /// Dropped instead of executed if this returns `false` when the event comes due.
+    guard: Option<Guard<S>>,
+    /// The periodic series this occurrence belongs to, if any.
+    series: Option<Rc<Series<E, T>>>,
     event: E,
     _marker: PhantomData<S>,
 }
 
-impl<S, E: Event<S>> Scheduled<S, E> {
-    fn new(event: E) -> Self {
-        let at = event.time();
+impl<S, E, T: SimTime> Scheduled<S, E, T> {
+    /// Wrap an event that fires at `at`, which may differ from its own `Event::time()`.
+    fn new(at: T, rank: i64, seq: u64, parent: Option<EventHandle>, event: E) -> Self {
         Self {
             at,
+            rank,
+            seq,
+            parent,
+            guard: None,
+            series: None,
             event,
             _marker: PhantomData,
         }
     }
+
+    /// The handle `State::cancel` matches: the series' for periodic occurrences.
+    fn cancel_handle(&self) -> EventHandle {
+        self.series
+            .as_ref()
+            .map_or(EventHandle(self.seq), |series| series.id)
+    }
+
+    fn key(&self) -> ScheduleKey<T> {
+        ScheduleKey {
+            at: self.at,
+            rank: self.rank,
+            seq: self.seq,
+        }
+    }
 }
 
-impl<S, E: Event<S>> PartialEq for Scheduled<S, E> {
+/// Equal only for the same queue entry: the key includes the unique `seq`, so distinct
+/// events at the same time and priority still compare unequal.
+impl<S, E, T: SimTime> PartialEq for Scheduled<S, E, T> {
     fn eq(&self, other: &Self) -> bool {
-        self.at.total_cmp(&other.at) == Ordering::Equal
+        self.cmp(other) == Ordering::Equal
     }
 }
-impl<S, E: Event<S>> Eq for Scheduled<S, E> {}
-impl<S, E: Event<S>> PartialOrd for Scheduled<S, E> {
+impl<S, E, T: SimTime> Eq for Scheduled<S, E, T> {}
+impl<S, E, T: SimTime> PartialOrd for Scheduled<S, E, T> {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.cmp(other))
     }
 }
-impl<S, E: Event<S>> Ord for Scheduled<S, E> {
+impl<S, E, T: SimTime> Ord for Scheduled<S, E, T> {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.at.total_cmp(&other.at).reverse()
+        // Reversed so the max-heap pops the earliest entry first.
+        self.key().cmp(&other.key()).reverse()
     }
 }
 
 /// The simulation state visible to events.
 #[derive(Clone)]
-pub struct State<S, E: Event<S>> {
-    now: Timestamp,
+pub struct State<S, E: Event<S, T, C>, T: SimTime = Timestamp, C = ()> {
+    now: T,
     data: S,
-    queue: BinaryHeap<Scheduled<S, E>>,
+    metrics: Metrics,
+    queue: Queue<S, E, T>,
+    /// Sequence number handed to the next scheduled event.
+    next_seq: u64,
+    /// The event being executed, if any.
+    current: Option<EventHandle>,
+    /// `Event::label` of the event being executed, if any.
+    current_label: Option<&'static str>,
+    /// Handles passed to `cancel` whose events have not come due yet.
+    cancelled: HashSet<EventHandle>,
+    /// Pending `(time, event)` pairs, when identical events are coalesced.
+    pending_set: Option<Box<dyn PendingSet<E, T>>>,
+    /// Integral of the number of pending events over simulated time since `stats_start`.
+    queue_area: f64,
+    /// Time from which time-weighted statistics are accumulated.
+    stats_start: T,
+    /// Number of executed events per `Event::label`.
+    event_counts: HashMap<&'static str, u64>,
+    /// Time at which statistics are reset, until the clock reaches it.
+    warmup: Option<T>,
+    /// The warm-up time was crossed and the engine has not yet reacted to it.
+    warmup_reached: bool,
+    /// Past-time events accepted through `schedule`/`schedule_at` (see `past_schedules`).
+    past_schedules: u64,
+    /// Horizon of the run in progress, if it has one.
+    horizon: Option<T>,
+    /// Resources registered with `add_resource`, indexed by `ResourceId`.
+    resources: Vec<Resource<E>>,
+    /// Errors returned by fallible events, with the times they failed at.
+    errors: Vec<(T, SimError)>,
+    /// Read-only configuration given to `Engine::with_context`.
+    context: C,
+    #[cfg(feature = "rand")]
+    rng: StdRng,
+    rules: Rules<E, T>,
+}
+
+/// Engine configuration applied when events are scheduled.
+///
+/// Kept apart from the simulation data so that restoring a snapshot does not also roll
+/// back the configuration.
+struct Rules<E, T> {
+    past_policy: PastEventPolicy,
+    /// Latest time an event may be scheduled for, if capped.
+    max_time: Option<T>,
+    /// Optional perturbation added to every schedule time.
+    #[cfg(feature = "rand")]
+    jitter: Option<JitterFn<T>>,
+    on_schedule: Option<ScheduleHook<E, T>>,
+}
+
+impl<E, T> Default for Rules<E, T> {
+    fn default() -> Self {
+        Self {
+            past_policy: PastEventPolicy::default(),
+            max_time: None,
+            #[cfg(feature = "rand")]
+            jitter: None,
+            on_schedule: None,
+        }
+    }
+}
+
+impl<E, T: Copy> Clone for Rules<E, T> {
+    fn clone(&self) -> Self {
+        Self {
+            past_policy: self.past_policy,
+            max_time: self.max_time,
+            #[cfg(feature = "rand")]
+            jitter: self.jitter.clone(),
+            on_schedule: self.on_schedule.clone(),
+        }
+    }
 }
 
-impl<S, E: Event<S>> State<S, E> {
+impl<S, E: Event<S, T>, T: SimTime> State<S, E, T> {
     /// Create a new simulation state with user data.
     pub fn new(data: S) -> Self {
+        Self::create(data, ())
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> State<S, E, T, C> {
+    fn create(data: S, context: C) -> Self {
         Self {
-            now: 0.0,
+            now: T::ZERO,
             data,
-            queue: BinaryHeap::new(),
+            metrics: Metrics::default(),
+            queue: Queue::new(QueueKind::BinaryHeap),
+            next_seq: 0,
+            current: None,
+            current_label: None,
+            cancelled: HashSet::new(),
+            pending_set: None,
+            queue_area: 0.0,
+            stats_start: T::ZERO,
+            event_counts: HashMap::new(),
+            warmup: None,
+            warmup_reached: false,
+            past_schedules: 0,
+            horizon: None,
+            resources: Vec::new(),
+            errors: Vec::new(),
+            context,
+            #[cfg(feature = "rand")]
+            rng: StdRng::seed_from_u64(0),
+            rules: Rules::default(),
         }
     }
 
     /// Current simulation time.
-    pub fn now(&self) -> Timestamp {
+    pub fn now(&self) -> T {
         self.now
     }
 
@@ -126,119 +530,4925 @@ impl<S, E: Event<S>> State<S, E> {
         &mut self.data
     }
 
+    /// Named metrics recorded so far.
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The read-only context passed to `Engine::with_context`; `()` for engines built
+    /// without one.
+    pub fn ctx(&self) -> &C {
+        &self.context
+    }
+
+    /// Mutable access to the named metrics.
+    pub fn metrics_mut(&mut self) -> &mut Metrics {
+        &mut self.metrics
+    }
+
+    /// The engine's random number generator, for service times, arrivals and the like.
+    ///
+    /// Seeded from `Engine::new_seeded` (seed 0 otherwise), so runs are reproducible.
+    /// The generator is part of the `State`, so restore points (see
+    /// `Engine::set_rewindable`) capture its position and `Engine::rewind` replays the
+    /// same draws. Requires the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Time of the earliest pending event, without removing it.
+    pub fn peek_next_time(&self) -> Option<T> {
+        self.queue.peek_min().map(|s| s.at)
+    }
+
+    /// Time limit of the run executing this event: the `until_time` of `run_until` and
+    /// the other horizon-bounded runs, or `None` under `run`, `step` and
+    /// `run_n_events`.
+    ///
+    /// Lets an event skip starting work that could not finish before the run ends.
+    pub fn horizon(&self) -> Option<T> {
+        self.horizon
+    }
+
+    /// Label of the event being executed (see `Event::label`), or `None` outside
+    /// `execute`.
+    ///
+    /// Lets helpers called from several events tell which one triggered them, e.g. to
+    /// log it or to act only on deliveries.
+    pub fn current_event_label(&self) -> Option<&'static str> {
+        self.current_label
+    }
+
+    /// Pre-size the pending queue for at least `additional` more events, e.g. before
+    /// seeding a known burst. Only a hint: the queue still grows past it as needed.
+    pub fn reserve(&mut self, additional: usize) {
+        self.queue.reserve(additional);
+    }
+
+    /// Number of pending events.
+    pub fn pending_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Whether no events are pending.
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
     /// Schedule an event at its own `Event::time()`.
+    ///
+    /// Convenience for `schedule_result` that discards the outcome. Because the caller
+    /// cannot see the outcome, a bad time is treated as a bug: debug builds panic on a
+    /// non-finite time (which release builds reject silently) and on a time earlier
+    /// than `now` that the past-event policy lets through (which release builds count
+    /// in `past_schedules`). Use `try_schedule` to handle such events instead.
     pub fn schedule(&mut self, event: E) {
-        self.queue.push(Scheduled::new(event));
+        self.check_time(event.time());
+        let _ = self.schedule_result(event);
     }
-}
 
-/// The engine drives the event loop and owns the `State`.
-pub struct Engine<S, E: Event<S>> {
-    state: State<S, E>,
-    /// Snapshots of the state after each executed event (and at start/end).
-    history: Vec<State<S, E>>,
-    /// Chronological event log: (time, label)
-    events: Vec<(Timestamp, String)>,
-}
+    /// Schedule every event from `events` at its own `Event::time()`, as `schedule` does.
+    ///
+    /// The queue is pre-sized from the iterator's size hint.
+    pub fn schedule_all(&mut self, events: impl IntoIterator<Item = E>) {
+        let events = events.into_iter();
+        self.reserve(events.size_hint().0);
+        for event in events {
+            self.schedule(event);
+        }
+    }
 
-impl<S: Clone, E: Event<S> + Clone + std::fmt::Debug> Engine<S, E> {
-    /// Create a new engine with initial user state.
-    pub fn new(data: S) -> Self {
-        let state = State::<S, E>::new(data);
-        let mut engine = Self {
-            state,
-            history: Vec::new(),
-            events: Vec::new(),
+    /// Schedule an event at its own `Event::time()`, refusing it if it lies before `now`
+    /// whatever the past-event policy.
+    ///
+    /// Events coalesced into an identical pending one count as scheduled.
+    pub fn try_schedule(&mut self, event: E) -> Result<(), ScheduleError> {
+        if event.time() < self.now {
+            return Err(ScheduleError::Past);
+        }
+        match self.schedule_result(event) {
+            ScheduleOutcome::Scheduled(_) | ScheduleOutcome::Coalesced => Ok(()),
+            ScheduleOutcome::RejectedPast => Err(ScheduleError::Past),
+            ScheduleOutcome::RejectedNonFinite => Err(ScheduleError::NonFinite),
+            ScheduleOutcome::RejectedBeyondMaxTime => Err(ScheduleError::BeyondMaxTime),
+        }
+    }
+
+    /// Number of events earlier than `now` that `schedule`, `schedule_at` or another
+    /// method flagging bad times like them accepted, plus those `Engine::run_with_source`
+    /// skipped.
+    ///
+    /// The former are never counted in debug builds, which panic on such events instead.
+    pub fn past_schedules(&self) -> u64 {
+        self.past_schedules
+    }
+
+    /// Schedule an event at its own `Event::time()` and report whether it was accepted.
+    ///
+    /// Events with a non-finite time are always rejected; events earlier than `now`
+    /// are rejected when the past-event policy is `PastEventPolicy::Error`, and events
+    /// later than the maximum time (see `Engine::set_max_time`) are rejected when one is set.
+    pub fn schedule_result(&mut self, event: E) -> ScheduleOutcome {
+        let (at, rank) = (event.time(), rank_of(&event));
+        self.enqueue(at, rank, event)
+    }
+
+    /// Schedule an event at `at`, ignoring its own `Event::time()`.
+    ///
+    /// Lets one event value be reused at different times, and events need not store
+    /// their own firing time. The event's priority still breaks ties. Subject to the
+    /// same checks as `schedule_result`, and past times are treated as in `schedule`.
+    pub fn schedule_at(&mut self, at: T, event: E) {
+        self.check_time(at);
+        let rank = rank_of(&event);
+        let _ = self.enqueue(at, rank, event);
+    }
+
+    /// Schedule `event` at `at`, to run only if `guard` still holds for the user data
+    /// when it comes due; otherwise it is silently dropped.
+    ///
+    /// Models "do this only if the precondition still holds", e.g. a worker dispatched
+    /// to fetch stock that may be gone by the time it arrives. Bad times are flagged as
+    /// in `schedule`; the outcome is that of `schedule_result`.
+    pub fn schedule_if(
+        &mut self,
+        at: T,
+        event: E,
+        guard: impl Fn(&S) -> bool + 'static,
+    ) -> ScheduleOutcome {
+        self.check_time(at);
+        let rank = rank_of(&event);
+        self.enqueue_with(at, rank, event, Some(Rc::new(guard)), None)
+    }
+
+    /// Fire the event built by `make` at `first`, `first + period`, `first + 2 * period`
+    /// and so on, until the series is cancelled by passing the returned handle to
+    /// `cancel`. `make` receives each occurrence's firing time.
+    ///
+    /// A bad `first` is flagged as in `schedule`, and the first occurrence's outcome is
+    /// that of `schedule_result`; a later occurrence that is rejected ends the series.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is not a positive, finite number, since the series would
+    /// otherwise fire forever at the same instant.
+    pub fn schedule_periodic(
+        &mut self,
+        first: T,
+        period: T,
+        make: impl Fn(T) -> E + 'static,
+    ) -> ScheduleOutcome {
+        assert!(
+            period > T::ZERO && period.is_finite(),
+            "period must be positive and finite, got {period:?}"
+        );
+        self.check_time(first);
+        let series = Rc::new(Series {
+            // The first occurrence takes the next sequence number if it is accepted.
+            id: EventHandle(self.next_seq),
+            period,
+            make: Box::new(make),
+        });
+        let event = (series.make)(first);
+        let rank = rank_of(&event);
+        self.enqueue_with(first, rank, event, None, Some(series))
+    }
+
+    /// Cancel a pending event, or stop a periodic series, by the handle it was
+    /// scheduled with.
+    ///
+    /// Cancellation is lazy: the entry stays in the queue (and in `pending_len`) until
+    /// it comes due, and is then dropped without running, calling `Event::on_cancel`
+    /// instead. At that point the clock still shows the previous event's time. Entries
+    /// cleared by `cancel_all` or `Engine::drain_remaining` before coming due are
+    /// dropped without the hook. Cancelling an event that already ran has no effect.
+    pub fn cancel(&mut self, handle: EventHandle) {
+        if handle.0 < self.next_seq {
+            self.cancelled.insert(handle);
+        }
+    }
+
+    /// Move a pending event, or the next occurrence of a periodic series, to `new_at`,
+    /// keeping the event value and its handle. Returns `false`, changing nothing, if no
+    /// such event is pending or `new_at` would be rejected by `schedule_result`. A bad
+    /// `new_at` is also flagged as in `schedule`.
+    ///
+    /// The moved event keeps its place in scheduling order, so among events with the
+    /// same time and priority it fires as if scheduled when it originally was. The
+    /// queue has no decrease-key, so this takes O(n) in the number of pending events.
+    pub fn reschedule(&mut self, handle: EventHandle, new_at: T) -> bool {
+        self.check_time(new_at);
+        if self.cancelled.contains(&handle) || self.rejection(new_at).is_some() {
+            return false;
+        }
+        let Some(mut scheduled) = self.queue.remove(&|s| s.cancel_handle() == handle) else {
+            return false;
         };
-        engine.history.push(engine.state.clone());
-        engine
+        if let Some(set) = &mut self.pending_set {
+            set.remove(scheduled.at, &scheduled.event);
+            if !set.insert(new_at, &scheduled.event) {
+                // An identical event is already pending there; merge into it.
+                return true;
+            }
+        }
+        scheduled.at = new_at;
+        self.queue.push(scheduled);
+        true
     }
 
-    /// Accessors to read the state and time (outside of events).
-    pub fn now(&self) -> Timestamp {
-        self.state.now()
+    /// Schedule `event` at the current time, after everything else at this instant.
+    ///
+    /// Events at the same timestamp normally fire by phase and priority, then in the
+    /// order they were scheduled. An event scheduled with this method instead fires
+    /// after every other event at `now`, whatever its phase and priority: those already
+    /// pending and those scheduled later at the same instant through any other method.
+    /// Several "last" events at one instant fire in the order they were scheduled.
+    /// `event.time()`, `event.phase()` and `event.priority()` are ignored.
+    pub fn schedule_now_last(&mut self, event: E) {
+        let _ = self.enqueue(self.now, RANK_LAST, event);
     }
-    pub fn state(&self) -> &S {
-        self.state.state()
+
+    /// Schedule the next occurrence of a recurring event `period` after `now`.
+    ///
+    /// Meant to be called at the end of a periodic event's `execute`. `make` receives
+    /// the next firing time and builds the event; it fires at `now + period` whatever
+    /// its own `Event::time()` says. The firing time is flagged as in `schedule` if bad,
+    /// e.g. when `now + period` overflows, and the outcome is that of `schedule_result`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is not a positive, finite number, since the worker would
+    /// otherwise fire forever at the same instant.
+    pub fn reschedule_self_every(
+        &mut self,
+        period: T,
+        make: impl FnOnce(T) -> E,
+    ) -> ScheduleOutcome {
+        assert!(
+            period > T::ZERO && period.is_finite(),
+            "reschedule period must be positive and finite, got {period:?}"
+        );
+        let at = self.now + period;
+        self.check_time(at);
+        let event = make(at);
+        let rank = rank_of(&event);
+        self.enqueue(at, rank, event)
     }
-    pub fn state_mut(&mut self) -> &mut S {
-        self.state.state_mut()
+
+    /// Hand out the next insertion sequence number.
+    fn take_seq(&mut self) -> u64 {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        seq
     }
 
-    /// Allow external scheduling prior to running.
-    pub fn schedule(&mut self, event: E) {
-        self.state.schedule(event)
+    /// Flag an unchecked schedule at a non-finite time, or into the past when the
+    /// past-event policy would accept it.
+    fn check_time(&mut self, at: T) {
+        if cfg!(debug_assertions) && !at.is_finite() {
+            panic!("event scheduled at non-finite time {at:?}");
+        }
+        if at < self.now && self.rules.past_policy == PastEventPolicy::Allow {
+            if cfg!(debug_assertions) {
+                panic!(
+                    "event scheduled at {at:?}, before the current time {:?}",
+                    self.now
+                );
+            }
+            self.past_schedules += 1;
+            #[cfg(feature = "tracing")]
+            tracing::warn!(at = ?at, now = ?self.now, "event scheduled into the past");
+        }
     }
 
-    /// Run until the queue is empty or the time limit is reached.
-    pub fn run_until(&mut self, until_time: Timestamp) {
-        while let Some(scheduled) = self.state.queue.pop() {
-            if scheduled.at > until_time {
-                self.state.queue.push(scheduled);
-                break;
+    /// Why an event at `at` may not be scheduled, if it may not.
+    fn rejection(&self, at: T) -> Option<ScheduleOutcome> {
+        if !at.is_finite() {
+            Some(ScheduleOutcome::RejectedNonFinite)
+        } else if at < self.now && self.rules.past_policy == PastEventPolicy::Error {
+            Some(ScheduleOutcome::RejectedPast)
+        } else if self.rules.max_time.is_some_and(|cap| at > cap) {
+            Some(ScheduleOutcome::RejectedBeyondMaxTime)
+        } else {
+            None
+        }
+    }
+
+    /// Enqueue an event from an external source (see `Engine::run_with_source`).
+    ///
+    /// Unlike `schedule`, a time earlier than `now` is not a bug here but bad input: the
+    /// event is dropped and counted in `past_schedules` whatever the past-event policy.
+    fn schedule_external(&mut self, event: E) -> ScheduleOutcome {
+        let at = event.time();
+        if at < self.now {
+            self.past_schedules += 1;
+            #[cfg(feature = "tracing")]
+            tracing::warn!(at = ?at, now = ?self.now, "source event in the past skipped");
+            return ScheduleOutcome::RejectedPast;
+        }
+        let rank = rank_of(&event);
+        self.enqueue(at, rank, event)
+    }
+
+    /// Validate and enqueue `event` at `at`, applying any schedule-time perturbation.
+    fn enqueue(&mut self, at: T, rank: i64, event: E) -> ScheduleOutcome {
+        self.enqueue_with(at, rank, event, None, None)
+    }
+
+    fn enqueue_with(
+        &mut self,
+        at: T,
+        rank: i64,
+        event: E,
+        guard: Option<Guard<S>>,
+        series: Option<Rc<Series<E, T>>>,
+    ) -> ScheduleOutcome {
+        if let Some(rejected) = self.rejection(at) {
+            return rejected;
+        }
+        // Jitter never changes whether an event is accepted: the perturbed time is
+        // kept between `now` and the time cap.
+        #[cfg(feature = "rand")]
+        let at = match &self.rules.jitter {
+            Some(jitter) => {
+                let jittered = at + jitter(&mut self.rng);
+                match self.rules.max_time {
+                    Some(cap) if jittered > cap => cap,
+                    _ if jittered < self.now => self.now,
+                    _ => jittered,
+                }
+            }
+            None => at,
+        };
+        if let Some(set) = &mut self.pending_set {
+            if !set.insert(at, &event) {
+                return ScheduleOutcome::Coalesced;
             }
-            self.state.now = scheduled.at;
-            // Log the event before execution
-            self.events
-                .push((self.state.now, format!("{:?}", scheduled.event)));
-            scheduled.event.execute(&mut self.state);
-            self.history.push(self.state.clone());
         }
-        if self.state.now < until_time {
-            self.state.now = until_time;
+        if let Some(hook) = &self.rules.on_schedule {
+            (hook.borrow_mut())(self.now, &event);
         }
-        if self.history.last().map(|s| s.now) != Some(self.state.now) {
-            self.history.push(self.state.clone());
+        let seq = self.take_seq();
+        let mut scheduled = Scheduled::new(at, rank, seq, self.current, event);
+        scheduled.guard = guard;
+        scheduled.series = series;
+        self.queue.push(scheduled);
+        ScheduleOutcome::Scheduled(EventHandle(seq))
+    }
+
+    /// Cancel every pending event, leaving the queue empty.
+    ///
+    /// With `run_cleanup`, each event's `Event::on_cancel` runs in firing order before it
+    /// is discarded; events that the cleanup code itself schedules stay pending.
+    /// Called from an event's `execute`, e.g. a shutdown, this ends the whole cascade:
+    /// only events scheduled after the call remain.
+    pub fn cancel_all(&mut self, run_cleanup: bool) {
+        let pending = self.take_pending();
+        if run_cleanup {
+            for scheduled in pending {
+                scheduled.event.on_cancel(self);
+            }
         }
     }
 
-    /// Access the recorded state snapshots.
-    pub fn history(&self) -> &[State<S, E>] {
-        &self.history
+    /// Drop every pending event without running `Event::on_cancel`, e.g. from a
+    /// shutdown event ending the simulation. Same as `cancel_all(false)`; use
+    /// `cancel_all(true)` when the cancelled events need cleaning up.
+    pub fn clear_queue(&mut self) {
+        self.cancel_all(false);
     }
 
-    /// Access the chronological event log.
-    pub fn events(&self) -> &[(Timestamp, String)] {
-        &self.events
+    /// Keep only the pending events for which `f(event, time)` returns `true`.
+    ///
+    /// The others are discarded without running `Event::on_cancel`, and dropping the
+    /// next occurrence of a periodic series ends the series. Entries already cancelled
+    /// are dropped too, running their `on_cancel` as when they come due. Events are
+    /// visited in no particular order. Takes O(n) in the number of pending events.
+    pub fn retain_pending(&mut self, mut f: impl FnMut(&E, T) -> bool) {
+        for scheduled in self.queue.drain() {
+            let cancelled = self.cancelled.remove(&scheduled.cancel_handle());
+            if !cancelled && f(&scheduled.event, scheduled.at) {
+                self.queue.push(scheduled);
+                continue;
+            }
+            if let Some(set) = &mut self.pending_set {
+                set.remove(scheduled.at, &scheduled.event);
+            }
+            if cancelled {
+                scheduled.event.on_cancel(self);
+            }
+        }
+    }
+
+    /// Move every pending event from its time `t` to `f(t)`, e.g. to delay everything
+    /// by a fixed amount after a disruption.
+    ///
+    /// Events keep their handles and scheduling order, so ties still resolve as before;
+    /// a periodic series continues from its moved occurrence. Events that land on an
+    /// identical pending event are merged into it when coalescing is enabled. Takes
+    /// O(n log n) in the number of pending events.
+    ///
+    /// # Panics
+    ///
+    /// If `f` returns a non-finite time or one before `now()`.
+    pub fn map_pending_times(&mut self, f: impl Fn(T) -> T) {
+        let mut pending = self.queue.drain();
+        if let Some(set) = &mut self.pending_set {
+            set.clear();
+        }
+        for scheduled in &mut pending {
+            let at = f(scheduled.at);
+            assert!(
+                at.is_finite() && at >= self.now,
+                "pending event moved from {:?} to {at:?}, which is not a finite time from now = {:?}",
+                scheduled.at,
+                self.now
+            );
+            scheduled.at = at;
+        }
+        for scheduled in pending {
+            if let Some(set) = &mut self.pending_set {
+                if !set.insert(scheduled.at, &scheduled.event) {
+                    continue;
+                }
+            }
+            self.queue.push(scheduled);
+        }
+    }
+
+    /// Empty the queue, returning the entries not cancelled in firing order.
+    fn take_pending(&mut self) -> Vec<Scheduled<S, E, T>> {
+        let mut pending = self.queue.drain();
+        if let Some(set) = &mut self.pending_set {
+            set.clear();
+        }
+        let cancelled = std::mem::take(&mut self.cancelled);
+        pending.retain(|scheduled| !cancelled.contains(&scheduled.cancel_handle()));
+        // Ascending by `Ord` is latest first.
+        pending.sort_unstable_by(|a, b| b.cmp(a));
+        pending
+    }
+
+    /// Pop the earliest pending event if it fires no later than `limit`, dropping guarded
+    /// events whose guard no longer holds on the way.
+    fn pop_due(&mut self, limit: T) -> Option<Scheduled<S, E, T>> {
+        loop {
+            let at = self.queue.peek_min()?.at;
+            if at > limit {
+                return None;
+            }
+            // The popped event was pending until its own time, so count it too.
+            self.integrate_queue(at);
+            let scheduled = self.queue.pop_min()?;
+            if let Some(set) = &mut self.pending_set {
+                set.remove(scheduled.at, &scheduled.event);
+            }
+            if !self.cancelled.is_empty() && self.cancelled.remove(&scheduled.cancel_handle()) {
+                scheduled.event.on_cancel(self);
+                continue;
+            }
+            if let Some(series) = &scheduled.series {
+                let next = scheduled.at + series.period;
+                let event = (series.make)(next);
+                let rank = rank_of(&event);
+                let _ = self.enqueue_with(next, rank, event, None, Some(Rc::clone(series)));
+            }
+            match &scheduled.guard {
+                Some(guard) if !guard(&self.data) => continue,
+                _ => return Some(scheduled),
+            }
+        }
+    }
+
+    /// Add the pending-event count times the time from `now` to `to` to the queue
+    /// length integral. Called right before the clock moves forward.
+    ///
+    /// If the move crosses the warm-up time, statistics gathered so far are dropped and
+    /// accumulation restarts at the warm-up time.
+    fn integrate_queue(&mut self, to: T) {
+        if let Some(warmup) = self.warmup.filter(|&w| w <= to) {
+            self.warmup = None;
+            self.warmup_reached = true;
+            self.queue_area = 0.0;
+            self.event_counts.clear();
+            self.stats_start = warmup;
+        }
+        let from = if self.stats_start > self.now {
+            self.stats_start
+        } else {
+            self.now
+        };
+        if to > from && !self.queue.is_empty() {
+            self.queue_area += self.queue.len() as f64 * (to - from).to_f64();
+        }
     }
 }
-#[cfg(test)]
-mod tests {
-    use super::*;
 
-    #[derive(Default, Clone)]
-    struct Counter {
-        ticks: u32,
+impl<S, E: Event<S, Timestamp, C>, C> State<S, E, Timestamp, C> {
+    /// Schedule `event` `delay` after `now`, ignoring its own `Event::time()`, with
+    /// the same checks as `schedule_at`.
+    pub fn schedule_after(&mut self, delay: Duration, event: E) {
+        self.schedule_at(self.now + delay, event);
     }
+}
 
-    #[derive(Clone, Debug)]
-    struct Tick {
-        at: Timestamp,
-        left: u32,
+impl<S, E: Event<S, Timestamp, C>, C> Engine<S, E, Timestamp, C> {
+    /// External counterpart of `State::schedule_after`.
+    pub fn schedule_after(&mut self, delay: Duration, event: E) {
+        self.state.schedule_after(delay, event)
     }
+}
 
-    impl Event<Counter> for Tick {
-        fn time(&self) -> Timestamp {
-            self.at
+#[cfg(feature = "rand")]
+impl<S, E: Event<S, Timestamp, C>, C> State<S, E, Timestamp, C> {
+    /// Draw an exponentially distributed delay with the given `rate` (mean `1 / rate`),
+    /// e.g. the gap between arrivals of a Poisson process. Uses `State::rng`.
+    ///
+    /// # Panics
+    ///
+    /// If `rate` is not a positive, finite number.
+    pub fn sample_exponential(&mut self, rate: f64) -> Timestamp {
+        assert!(
+            rate > 0.0 && rate.is_finite(),
+            "exponential rate must be positive and finite, got {rate}"
+        );
+        // Inverse CDF; `1 - u` lies in (0, 1], keeping the logarithm finite.
+        let u: f64 = rand::Rng::random(&mut self.rng);
+        -(1.0 - u).ln() / rate
+    }
+
+    /// Schedule the event built by `make` after an exponentially distributed delay
+    /// (see `sample_exponential`). `make` receives the firing time, which is flagged as
+    /// in `schedule` if bad; the outcome is that of `schedule_result`.
+    pub fn schedule_exponential(
+        &mut self,
+        rate: f64,
+        make: impl FnOnce(Timestamp) -> E,
+    ) -> ScheduleOutcome {
+        let at = self.now + self.sample_exponential(rate);
+        self.check_time(at);
+        let event = make(at);
+        let rank = rank_of(&event);
+        self.enqueue(at, rank, event)
+    }
+}
+
+/// Index of pending `(time, event)` pairs used to coalesce identical events.
+///
+/// Boxed behind a trait so that only engines that opt in need `E: Eq + Hash`.
+trait PendingSet<E, T> {
+    /// Record a pending pair; returns `false` if an identical one is already pending.
+    fn insert(&mut self, at: T, event: &E) -> bool;
+    fn remove(&mut self, at: T, event: &E);
+    fn clear(&mut self);
+    fn clone_box(&self) -> Box<dyn PendingSet<E, T>>;
+}
+
+impl<E, T> Clone for Box<dyn PendingSet<E, T>> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// `PendingSet` keyed by the event value, listing the times it is pending at.
+///
+/// Events are rarely pending at many times at once, so the per-event list stays short.
+#[derive(Clone)]
+struct IdenticalSet<E, T>(HashMap<E, Vec<T>>);
+
+impl<E: Eq + Hash + Clone + 'static, T: SimTime> PendingSet<E, T> for IdenticalSet<E, T> {
+    fn insert(&mut self, at: T, event: &E) -> bool {
+        let times = self.0.entry(event.clone()).or_default();
+        if times.iter().any(|t| t.cmp_time(&at).is_eq()) {
+            return false;
         }
-        fn execute(self, state: &mut State<Counter, Tick>) {
-            state.state_mut().ticks += 1;
-            if self.left > 0 {
-                let next_at = self.at + 0.5;
-                state.schedule(Tick {
-                    at: next_at,
-                    left: self.left - 1,
-                });
+        times.push(at);
+        true
+    }
+    fn remove(&mut self, at: T, event: &E) {
+        if let Some(times) = self.0.get_mut(event) {
+            times.retain(|t| t.cmp_time(&at).is_ne());
+            if times.is_empty() {
+                self.0.remove(event);
             }
         }
     }
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+    fn clone_box(&self) -> Box<dyn PendingSet<E, T>> {
+        Box::new(self.clone())
+    }
+}
 
-    #[test]
-    fn counter_advances() {
-        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
-        engine.schedule(Tick { at: 0.0, left: 3 });
-        engine.run_until(10.0);
-        assert_eq!(engine.state().ticks, 4);
-        assert!(engine.now() >= 10.0);
+/// The engine drives the event loop and owns the `State`.
+pub struct Engine<S, E: Event<S, T, C>, T: SimTime = Timestamp, C = ()> {
+    state: State<S, E, T, C>,
+    /// Time and user data after each executed event (and at start/end).
+    history: Vec<(T, S)>,
+    /// Copies the user data into `history`; `None` for engines built without history.
+    snapshot: Option<fn(&S) -> S>,
+    /// Bookkeeping for each `history` entry, index for index.
+    history_points: Vec<HistoryPoint<S, E, T, C>>,
+    /// Copies the full state into restore points, while rewinding is enabled.
+    restore: Option<RestoreFn<S, E, T, C>>,
+    /// Chronological event log: (time, description), when enabled.
+    events: Vec<(T, String)>,
+    /// Formats events for `events`; `None` while the string log is off.
+    describe: Option<fn(&E) -> String>,
+    /// Number of events executed when the string log was enabled.
+    events_from: usize,
+    /// Events the builder was told to expect; enabling the string log reserves this many.
+    expected_events: usize,
+    /// Slack allowed past the horizon when deciding whether an event is due.
+    horizon_epsilon: T,
+    /// Number of events executed so far.
+    processed: usize,
+    /// `(parent, child)` sequence numbers of the events executed while tracked.
+    causal: Vec<(u64, u64)>,
+    /// Whether `causal` is recorded (see `set_track_causality`).
+    track_causality: bool,
+    /// Number of events executed when causality tracking was enabled.
+    causal_from: usize,
+    /// Executed events with their times, when the typed log is enabled.
+    typed_events: Vec<(T, E)>,
+    /// Number of events executed when the typed log was enabled.
+    typed_from: usize,
+    /// Copies executed events into `typed_events`; `None` while the typed log is off.
+    copy_event: Option<fn(&E) -> E>,
+    /// Wall-clock timing per event label, when profiling is enabled.
+    profile: Option<HashMap<&'static str, ProfileEntry>>,
+    /// Maximum number of snapshots `history` retains (`None` keeps all of them).
+    history_capacity: Option<usize>,
+    /// Minimum simulated time between two snapshots, when history is sampled.
+    sample_interval: Option<T>,
+    /// A snapshot was skipped by sampling since the last one was recorded.
+    unsampled: bool,
+    /// Equality on user data, set when snapshots equal to the previous one are skipped.
+    skip_unchanged: Option<fn(&S, &S) -> bool>,
+    /// Drop history and event logs recorded before the warm-up time once it is reached.
+    trim_at_warmup: bool,
+    /// Projections registered with `track`.
+    trackers: Vec<Tracker<S, T>>,
+    /// Called with the event's time and the user data right before each execution.
+    before: Option<ExecuteHook<S, T>>,
+    /// Called with the event's time and the user data right after each execution.
+    after: Option<ExecuteHook<S, T>>,
+    /// Caller-attached metadata, one value per type.
+    meta: HashMap<TypeId, Box<dyn Any>>,
+    /// The warm-up time as last set, so `reset` can arm it again.
+    warmup: Option<T>,
+    /// Histogram of the gaps between consecutive executed events, when enabled.
+    gaps: Option<GapHistogram<T>>,
+    /// Time markers not yet reached, in ascending time order.
+    markers: Vec<(T, MarkerFn<S>)>,
+    /// Longest run of consecutive events at one instant a run tolerates, if limited.
+    max_zero_delay_chain: Option<usize>,
+    /// The instant of the last executed event and how many events in a row ran at it.
+    zero_delay_chain: (T, usize),
+    /// Date of time 0 and length of one time unit, set by `with_epoch`.
+    #[cfg(feature = "chrono")]
+    epoch: Option<(chrono::DateTime<chrono::Utc>, chrono::TimeDelta)>,
+    /// Whether a run stops at the first failed event.
+    error_policy: ErrorPolicy,
+    /// The last executed event recorded an error.
+    failed: bool,
+}
+
+/// What `rewind` needs to know about one history entry.
+struct HistoryPoint<S, E: Event<S, T, C>, T: SimTime, C> {
+    /// Number of events executed when the snapshot was taken.
+    processed: usize,
+    /// Metrics at the time of the snapshot.
+    metrics: Metrics,
+    /// Full state to return to, recorded while rewinding is enabled.
+    state: Option<Box<State<S, E, T, C>>>,
+}
+
+/// A scalar projection of the user data integrated over simulated time.
+struct Tracker<S, T> {
+    name: String,
+    project: Box<dyn Fn(&S) -> f64>,
+    /// Integral of `project` since `since`.
+    area: f64,
+    since: T,
+}
+
+/// Counts of inter-event gaps in buckets of `width` simulated time units.
+struct GapHistogram<T> {
+    width: f64,
+    counts: Vec<u64>,
+    /// Time of the last executed event.
+    last: Option<T>,
+}
+
+/// Accumulated wall-clock time spent executing one kind of event.
+#[derive(Clone, Copy, Debug, Default)]
+struct ProfileEntry {
+    total: std::time::Duration,
+    max: std::time::Duration,
+    count: u64,
+}
+
+/// Why a run returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The next pending event lies beyond the horizon.
+    HorizonReached,
+    /// No events are left to execute.
+    QueueEmpty,
+    /// An event matching the caller's predicate has just executed.
+    MatchedEvent,
+    /// The caller's stop condition on the state became true.
+    Predicate,
+    /// The observed metric settled within the caller's tolerance.
+    Stable,
+    /// The run executed as many events as it was allowed to.
+    EventBudget,
+    /// More consecutive events ran at one instant than `set_max_zero_delay_chain`
+    /// allows, which usually means events keep rescheduling each other with no delay.
+    ZeroDelayLoop,
+    /// An event failed (see `FallibleEvent`) and the error policy is
+    /// `ErrorPolicy::Abort`.
+    EventFailed,
+}
+
+/// Summary of a finished `run_until` call.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RunOutcome<T = Timestamp> {
+    /// The clock when the run returned: the horizon, unless it lay behind the clock,
+    /// was infinite (the last event's time then) or the run stopped early.
+    pub stopped_at: T,
+    /// `QueueEmpty` if no events are left, `HorizonReached` if some lie beyond the
+    /// horizon, so the results do not cover them. Runs can also stop early, e.g. with
+    /// `ZeroDelayLoop`.
+    pub reason: StopReason,
+    /// Number of events executed by this run.
+    pub events_executed: usize,
+}
+
+/// Configures an `Engine` before it is created (see `Engine::builder`).
+pub struct EngineBuilder<S, E: Event<S, T>, T: SimTime = Timestamp> {
+    data: S,
+    /// Expected number of executed events, used to pre-size per-event buffers.
+    expected_events: usize,
+    record_history: bool,
+    pending_set: Option<Box<dyn PendingSet<E, T>>>,
+    queue: QueueKind,
+}
+
+impl<S: Clone, E: Event<S, T> + Clone + Eq + Hash + 'static, T: SimTime> EngineBuilder<S, E, T> {
+    /// Coalesce identical events: scheduling an event equal to one already pending at
+    /// the same time is skipped with `ScheduleOutcome::Coalesced`.
+    ///
+    /// The engine keeps a hash map holding a clone of every pending event, roughly
+    /// doubling the memory used by the queue, and hashes each event on schedule and pop.
+    pub fn dedup_identical(mut self, enabled: bool) -> Self {
+        self.pending_set = if enabled {
+            Some(Box::new(IdenticalSet(HashMap::new())))
+        } else {
+            None
+        };
+        self
+    }
+}
+
+impl<S: Clone, E: Event<S>> EngineBuilder<S, E> {
+    /// Pre-size the engine's buffers for a run to `horizon` at roughly
+    /// `events_per_unit` events per time unit.
+    ///
+    /// History grows by one entry per executed event and the pending queue may hold as
+    /// many, so both reserve `horizon * events_per_unit` entries up front instead of
+    /// reallocating mid-run. The string event log, off by default, reserves the same
+    /// amount once `Engine::set_event_log` enables it.
+    pub fn estimate(mut self, horizon: Timestamp, events_per_unit: f64) -> Self {
+        self.expected_events = (horizon * events_per_unit).max(0.0).ceil() as usize;
+        self
+    }
+}
+
+impl<S: Clone, E: Event<S, T>, T: SimTime> EngineBuilder<S, E, T> {
+    /// Whether to snapshot the user data after every event (the default).
+    ///
+    /// Each snapshot clones the user data, which dominates memory and runtime on long
+    /// runs. When disabled, `history()` stays empty and `rewind` always fails. The
+    /// engine then keeps nothing per executed event unless asked to: the event logs
+    /// and causality tracking (see `Engine::set_track_causality`) are off by default.
+    pub fn record_history(mut self, enabled: bool) -> Self {
+        self.record_history = enabled;
+        self
+    }
+
+    /// Choose the data structure that holds pending events. Defaults to
+    /// `QueueKind::BinaryHeap`; the firing order is the same for every kind.
+    pub fn queue(mut self, kind: QueueKind) -> Self {
+        self.queue = kind;
+        self
+    }
+
+    /// Create the configured engine.
+    pub fn build(self) -> Engine<S, E, T> {
+        let mut engine = Engine::new(self.data);
+        engine.state.pending_set = self.pending_set;
+        engine.state.queue = Queue::with_capacity(self.queue, self.expected_events);
+        if self.record_history {
+            // Retake the initial snapshot so it reflects the configured state.
+            engine.history.clear();
+            engine.history_points.clear();
+            // One snapshot per event plus the closing snapshot at the horizon.
+            engine.history.reserve(self.expected_events + 1);
+            engine.history_points.reserve(self.expected_events + 1);
+            engine.record_snapshot();
+        } else {
+            engine.history_capacity = Some(0);
+            engine.history = Vec::new();
+            engine.history_points = Vec::new();
+        }
+        engine.expected_events = self.expected_events;
+        engine
+    }
+}
+
+impl<S: Clone, E: Event<S, T>, T: SimTime> Engine<S, E, T> {
+    /// Start configuring an engine with initial user state.
+    pub fn builder(data: S) -> EngineBuilder<S, E, T> {
+        EngineBuilder {
+            data,
+            expected_events: 0,
+            record_history: true,
+            pending_set: None,
+            queue: QueueKind::default(),
+        }
+    }
+
+    /// Create a new engine with initial user state.
+    pub fn new(data: S) -> Self {
+        Self::new_at(data, T::ZERO)
+    }
+
+    /// Create a new engine whose pending queue has room for `cap` events up front (see
+    /// `State::reserve`).
+    pub fn with_queue_capacity(data: S, cap: usize) -> Self {
+        let mut engine = Self::new(data);
+        engine.state.reserve(cap);
+        engine
+    }
+
+    /// Create a new engine whose clock starts at `start` instead of 0.
+    ///
+    /// Seeded events earlier than `start` are subject to the past-event policy: with
+    /// `PastEventPolicy::Error` they are rejected as soon as they are scheduled, which
+    /// catches setups that seed at `t=0` but start the clock later.
+    pub fn new_at(data: S, start: T) -> Self {
+        Self::create(data, start, Some(S::clone), ())
+    }
+
+    /// Create a new engine whose random number generator (see `State::rng`) is seeded
+    /// with `seed`. Requires the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn new_seeded(data: S, seed: u64) -> Self {
+        let mut engine = Self::new(data);
+        engine.state.rng = StdRng::seed_from_u64(seed);
+        engine
+    }
+
+    /// Re-run a recorded typed log (see `set_typed_log`) on fresh `data`, executing each
+    /// event at its logged time in log order, regardless of how the queue would order it.
+    ///
+    /// Comparing the result with the original run isolates bugs that come from
+    /// scheduling rather than from the events themselves. Events scheduled during the
+    /// replay are dropped at the end, since the log already decides what runs.
+    pub fn replay(data: S, log: &[(T, E)]) -> Self
+    where
+        E: Clone,
+    {
+        let mut engine = Self::new(data);
+        for (at, event) in log {
+            let seq = engine.state.next_seq;
+            engine.state.next_seq += 1;
+            engine.execute(Scheduled::new(*at, 0, seq, None, event.clone()));
+        }
+        engine.state.queue.drain();
+        engine
+    }
+}
+
+impl<S: Clone, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// Create a new engine with read-only configuration `ctx` next to the user state.
+    ///
+    /// Events read it with `State::ctx`; their `Event` impl names `C` as its context
+    /// type. History snapshots clone only `data`, never `ctx`, so parameters that never
+    /// change during a run belong here rather than in `S`. Only restore points (see
+    /// `set_rewindable`) copy it, along with the rest of the `State`.
+    pub fn with_context(ctx: C, data: S) -> Self {
+        Self::create(data, T::ZERO, Some(S::clone), ctx)
+    }
+}
+
+impl<S: Clone, E: Event<S, T, C> + Clone, T: SimTime, C: Clone> Engine<S, E, T, C> {
+    /// Keep a full copy of the state, pending queue included, next to every history
+    /// snapshot from now on, so `rewind` and `rewind_to` can return to it. Off by
+    /// default.
+    ///
+    /// History itself only copies the user data; restore points clone the whole
+    /// `State`, context included, which costs much more on models with long queues.
+    /// Disabling drops the restore points taken so far.
+    pub fn set_rewindable(&mut self, enabled: bool) {
+        if !enabled {
+            self.restore = None;
+            for point in &mut self.history_points {
+                point.state = None;
+            }
+            return;
+        }
+        let restore: RestoreFn<S, E, T, C> = State::clone;
+        self.restore = Some(restore);
+        // The latest snapshot may already describe the current state.
+        let current = (self.history.last(), self.history_points.last());
+        if let (Some((now, _)), Some(point)) = current {
+            if point.processed == self.processed && *now == self.state.now {
+                let state = Box::new(restore(&self.state));
+                if let Some(point) = self.history_points.last_mut() {
+                    point.state.get_or_insert(state);
+                }
+            }
+        }
+    }
+
+    /// Count the events a `run_until(until_time)` would execute, without running it.
+    ///
+    /// The run happens against a clone of the current state that is then discarded, so
+    /// the engine (state, queue, history and logs) is left untouched. Like the real run,
+    /// the count stops at a failed event under `ErrorPolicy::Abort` or at a zero-delay
+    /// loop (see `set_max_zero_delay_chain`).
+    pub fn dry_run_count(&self, until_time: T) -> usize {
+        let Some(limit) = self.due_limit(until_time) else {
+            return 0;
+        };
+        let mut scratch = self.state.clone();
+        scratch.rules.on_schedule = None;
+        scratch.horizon = Some(until_time);
+        let mut chain = self.zero_delay_chain;
+        let mut count = 0;
+        while let Some(scheduled) = scratch.pop_due(limit) {
+            chain = match chain {
+                (at, n) if at == scheduled.at => (at, n + 1),
+                _ => (scheduled.at, 1),
+            };
+            scratch.now = scheduled.at;
+            let errors = scratch.errors.len();
+            scheduled.event.execute(&mut scratch);
+            count += 1;
+            let failed = scratch.errors.len() > errors;
+            if (failed && self.error_policy == ErrorPolicy::Abort)
+                || self.max_zero_delay_chain.is_some_and(|max| chain.1 > max)
+            {
+                break;
+            }
+        }
+        count
+    }
+}
+
+impl<S: Clone, E: Event<S, T, C> + Clone, T: SimTime, C> Engine<S, E, T, C> {
+    /// Turn the typed event log (see `events_typed`) on or off.
+    ///
+    /// The log keeps a clone of every event executed while it is on. Disabling
+    /// discards the entries gathered so far.
+    pub fn set_typed_log(&mut self, enabled: bool) {
+        self.typed_events.clear();
+        self.typed_from = self.processed;
+        self.copy_event = enabled.then_some(E::clone as fn(&E) -> E);
+    }
+}
+
+impl<S, E: Event<S, T>, T: SimTime> Engine<S, E, T> {
+    /// Create an engine that never records history, so neither the user state nor the
+    /// events need to implement `Clone`.
+    ///
+    /// `history()` stays empty, `rewind` always fails and the typed log is unavailable;
+    /// everything else works as with `new`. As with `EngineBuilder::record_history`,
+    /// memory use then stays flat over a run unless a log or causality tracking is
+    /// turned on.
+    pub fn without_history(data: S) -> Self {
+        Self::create(data, T::ZERO, None, ())
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    fn create(data: S, start: T, snapshot: Option<fn(&S) -> S>, context: C) -> Self {
+        let mut state = State::create(data, context);
+        state.now = start;
+        state.stats_start = start;
+        let mut engine = Self {
+            state,
+            history: Vec::new(),
+            snapshot,
+            history_points: Vec::new(),
+            restore: None,
+            events: Vec::new(),
+            describe: None,
+            events_from: 0,
+            expected_events: 0,
+            horizon_epsilon: T::ZERO,
+            processed: 0,
+            causal: Vec::new(),
+            track_causality: false,
+            causal_from: 0,
+            typed_events: Vec::new(),
+            typed_from: 0,
+            copy_event: None,
+            profile: None,
+            history_capacity: None,
+            sample_interval: None,
+            unsampled: false,
+            skip_unchanged: None,
+            trim_at_warmup: false,
+            trackers: Vec::new(),
+            before: None,
+            after: None,
+            meta: HashMap::new(),
+            warmup: None,
+            gaps: None,
+            markers: Vec::new(),
+            max_zero_delay_chain: None,
+            zero_delay_chain: (start, 0),
+            #[cfg(feature = "chrono")]
+            epoch: None,
+            error_policy: ErrorPolicy::default(),
+            failed: false,
+        };
+        engine.record_snapshot();
+        engine
+    }
+
+    /// Start over from `data` at time 0, reusing this engine's allocations.
+    ///
+    /// The queue, history, event logs, metrics, statistics and counters are cleared
+    /// (keeping their capacity), registered resources are freed, and the initial
+    /// snapshot is recorded again. Configuration is kept: history and log settings,
+    /// policies, hooks, tracked projections, the warm-up time, metadata and the context.
+    /// The random number generator is not reseeded; it carries on from where the last
+    /// run left it.
+    pub fn reset(&mut self, data: S) {
+        let state = &mut self.state;
+        state.now = T::ZERO;
+        state.data = data;
+        state.metrics = Metrics::default();
+        state.queue.clear();
+        state.next_seq = 0;
+        state.current = None;
+        state.current_label = None;
+        state.cancelled.clear();
+        if let Some(set) = &mut state.pending_set {
+            set.clear();
+        }
+        state.queue_area = 0.0;
+        state.stats_start = T::ZERO;
+        state.event_counts.clear();
+        state.warmup = self.warmup;
+        state.warmup_reached = false;
+        state.past_schedules = 0;
+        state.horizon = None;
+        state.errors.clear();
+        for resource in &mut state.resources {
+            resource.reset();
+        }
+        self.history.clear();
+        self.history_points.clear();
+        self.events.clear();
+        self.events_from = 0;
+        self.processed = 0;
+        self.causal.clear();
+        self.causal_from = 0;
+        self.typed_events.clear();
+        self.typed_from = 0;
+        if let Some(profile) = &mut self.profile {
+            profile.clear();
+        }
+        if let Some(gaps) = &mut self.gaps {
+            gaps.counts.clear();
+            gaps.last = None;
+        }
+        self.zero_delay_chain = (T::ZERO, 0);
+        self.failed = false;
+        self.unsampled = false;
+        for tracker in &mut self.trackers {
+            tracker.area = 0.0;
+            tracker.since = T::ZERO;
+        }
+        self.record_snapshot();
+    }
+
+    /// Accessors to read the state and time (outside of events).
+    pub fn now(&self) -> T {
+        self.state.now()
+    }
+    pub fn state(&self) -> &S {
+        self.state.state()
+    }
+    pub fn state_mut(&mut self) -> &mut S {
+        self.state.state_mut()
+    }
+    pub fn metrics(&self) -> &Metrics {
+        self.state.metrics()
+    }
+    pub fn ctx(&self) -> &C {
+        self.state.ctx()
+    }
+
+    /// Allow external scheduling prior to running.
+    pub fn schedule(&mut self, event: E) {
+        self.state.schedule(event)
+    }
+
+    /// External counterpart of `State::schedule_result`.
+    pub fn schedule_result(&mut self, event: E) -> ScheduleOutcome {
+        self.state.schedule_result(event)
+    }
+
+    /// External counterpart of `State::schedule_all`.
+    pub fn schedule_all(&mut self, events: impl IntoIterator<Item = E>) {
+        self.state.schedule_all(events)
+    }
+
+    /// External counterpart of `State::try_schedule`.
+    pub fn try_schedule(&mut self, event: E) -> Result<(), ScheduleError> {
+        self.state.try_schedule(event)
+    }
+
+    /// External counterpart of `State::past_schedules`.
+    pub fn past_schedules(&self) -> u64 {
+        self.state.past_schedules()
+    }
+
+    /// External counterpart of `State::schedule_at`.
+    pub fn schedule_at(&mut self, at: T, event: E) {
+        self.state.schedule_at(at, event)
+    }
+
+    /// External counterpart of `State::schedule_if`.
+    pub fn schedule_if(
+        &mut self,
+        at: T,
+        event: E,
+        guard: impl Fn(&S) -> bool + 'static,
+    ) -> ScheduleOutcome {
+        self.state.schedule_if(at, event, guard)
+    }
+
+    /// External counterpart of `State::schedule_periodic`.
+    pub fn schedule_periodic(
+        &mut self,
+        first: T,
+        period: T,
+        make: impl Fn(T) -> E + 'static,
+    ) -> ScheduleOutcome {
+        self.state.schedule_periodic(first, period, make)
+    }
+
+    /// External counterpart of `State::cancel`.
+    pub fn cancel(&mut self, handle: EventHandle) {
+        self.state.cancel(handle)
+    }
+
+    /// External counterpart of `State::reschedule`.
+    pub fn reschedule(&mut self, handle: EventHandle, new_at: T) -> bool {
+        self.state.reschedule(handle, new_at)
+    }
+
+    /// Remove every pending event from the queue and return it with its firing time,
+    /// in the order the events would have fired.
+    ///
+    /// Cancelled events are left out; guarded events are included whether or not their
+    /// guard would hold, and periodic series end with the occurrence returned. Useful
+    /// to see what a run left undone, or to seed a continuation engine.
+    pub fn drain_remaining(&mut self) -> Vec<(T, E)> {
+        self.state
+            .take_pending()
+            .into_iter()
+            .map(|scheduled| (scheduled.at, scheduled.event))
+            .collect()
+    }
+
+    /// External counterpart of `State::cancel_all`.
+    pub fn cancel_all(&mut self, run_cleanup: bool) {
+        self.state.cancel_all(run_cleanup)
+    }
+
+    /// External counterpart of `State::retain_pending`.
+    pub fn retain_pending(&mut self, f: impl FnMut(&E, T) -> bool) {
+        self.state.retain_pending(f)
+    }
+
+    /// External counterpart of `State::map_pending_times`.
+    pub fn map_pending_times(&mut self, f: impl Fn(T) -> T) {
+        self.state.map_pending_times(f)
+    }
+
+    /// Choose how events scheduled before the current time are handled.
+    pub fn set_past_event_policy(&mut self, policy: PastEventPolicy) {
+        self.state.rules.past_policy = policy;
+    }
+
+    /// Call `f` with the current time and the event whenever an event is accepted into
+    /// the queue, whether scheduled from outside or by an executing event.
+    ///
+    /// Complements the execution log with the causal side: who scheduled what, when.
+    pub fn on_schedule(&mut self, f: impl FnMut(T, &E) + 'static) {
+        self.state.rules.on_schedule = Some(Rc::new(RefCell::new(f)));
+    }
+
+    /// Call `f` with the event's time and the user data right before every executed
+    /// event, so instrumentation can observe each step without touching `execute`.
+    /// Replaces any previous before-hook.
+    pub fn on_before(&mut self, f: impl FnMut(T, &S) + 'static) {
+        self.before = Some(Box::new(f));
+    }
+
+    /// Call `f` with the event's time and the resulting user data right after every
+    /// executed event. Replaces any previous after-hook.
+    pub fn on_after(&mut self, f: impl FnMut(T, &S) + 'static) {
+        self.after = Some(Box::new(f));
+    }
+
+    /// Call `cb` once, with the user data, when the clock first reaches or passes `t`,
+    /// whether or not an event lands there.
+    ///
+    /// The callback sees the data as it stood at `t`: it runs before any event at or
+    /// after `t` executes. Markers passed in one clock move fire in ascending time
+    /// order, markers at the same time in the order they were added. A marker behind
+    /// the clock fires at the next clock move.
+    pub fn add_time_marker(&mut self, t: T, cb: impl FnMut(&S) + 'static) {
+        let at = self.markers.partition_point(|(time, _)| *time <= t);
+        self.markers.insert(at, (t, Box::new(cb)));
+    }
+
+    /// Refuse to schedule events later than `cap` (`None` removes the cap).
+    ///
+    /// A guard against runaway delay computations: such events are rejected with
+    /// `ScheduleOutcome::RejectedBeyondMaxTime` instead of sitting in the queue forever.
+    pub fn set_max_time(&mut self, cap: Option<T>) {
+        self.state.rules.max_time = cap;
+    }
+
+    /// Discard the startup transient: when the clock first reaches `at`, the engine's
+    /// statistics (`mean_queue_length`, `event_counts`, `time_average`) restart from
+    /// zero while the simulation itself carries on. User data and `Metrics` are left
+    /// alone.
+    ///
+    /// A warm-up time already in the past takes effect at the next clock move.
+    pub fn set_warmup(&mut self, at: T) {
+        self.warmup = Some(at);
+        self.state.warmup = Some(at);
+    }
+
+    /// Whether reaching the warm-up time also drops the history snapshots and event
+    /// logs recorded so far, so they only describe the steady state. Off by default.
+    pub fn set_warmup_trims_logs(&mut self, trim: bool) {
+        self.trim_at_warmup = trim;
+    }
+
+    /// Tolerate events up to `eps` past the horizon in `run_until`.
+    ///
+    /// Timestamps computed by summing many delays drift slightly (e.g. `10.0000000001`
+    /// instead of `10.0`); a small epsilon keeps such events inside the run. Defaults to 0.
+    pub fn set_horizon_epsilon(&mut self, eps: T) {
+        self.horizon_epsilon = eps;
+    }
+
+    /// Abort a run once more than `n` consecutive events have executed at the same
+    /// instant, reporting `StopReason::ZeroDelayLoop` with the clock left at that
+    /// instant.
+    ///
+    /// An event that reschedules itself (or a partner) with no delay otherwise spins
+    /// forever while the clock never advances; this turns the hang into a diagnosable
+    /// stop. Off by default. Every driver honours it, `run` and `run_n_events`
+    /// included. The chain carries over between runs, so a run resumed at the stuck
+    /// instant stops again after one more event.
+    pub fn set_max_zero_delay_chain(&mut self, n: usize) {
+        self.max_zero_delay_chain = Some(n);
+    }
+
+    /// Perturb every subsequently scheduled event by a delay sampled from `f`.
+    ///
+    /// The sample is drawn from the engine's seeded RNG and added to the event's time
+    /// when it is scheduled; results are clamped to `now` so an event is never
+    /// scheduled into the past, and to the time cap (see `set_max_time`) so a jittered
+    /// event is never rejected. Requires the `rand` feature.
+    #[cfg(feature = "rand")]
+    pub fn set_schedule_jitter(&mut self, f: impl Fn(&mut StdRng) -> T + 'static) {
+        self.state.rules.jitter = Some(Rc::new(f));
+    }
+
+    /// Run until the queue is empty or the time limit is reached.
+    ///
+    /// The horizon is inclusive: events at exactly `until_time` execute, after which the
+    /// clock is advanced to `until_time` (see `run_until_exclusive` to leave them
+    /// pending). A horizon earlier than `now()` is a no-op. The returned `RunOutcome`
+    /// tells whether events remain beyond the horizon.
+    ///
+    /// `run_until(f64::INFINITY)` is the idiomatic way to run to completion: it
+    /// executes events until the queue is empty and leaves the clock at the last
+    /// event's time, so statistics and history end there rather than at infinity.
+    ///
+    /// # Panics
+    ///
+    /// If `until_time` is NaN. The same holds for every method taking a horizon.
+    pub fn run_until(&mut self, until_time: T) -> RunOutcome<T> {
+        let start = self.processed;
+        if let Some(limit) = self.start_run(until_time) {
+            while let Some(scheduled) = self.state.pop_due(limit) {
+                self.execute(scheduled);
+                if let Some(reason) = self.interruption() {
+                    return self.interrupted(start, reason);
+                }
+            }
+            self.finish(until_time);
+        }
+        self.outcome(start)
+    }
+
+    /// Like `run_until`, but report progress through `cb` every `every` time units.
+    ///
+    /// The run from `now()` to `until_time` is cut into slices of length `every`, and
+    /// `cb` receives the fraction of the run completed (in `(0, 1]`) each time the clock
+    /// reaches a slice boundary, and once more with `1.0` at the end of the run if
+    /// `until_time` is not itself a boundary. Updates are thus proportional to simulated
+    /// time however unevenly the events are spread: a single event that jumps over
+    /// several boundaries yields one call per boundary. A run to the current time
+    /// reports `1.0` once.
+    ///
+    /// # Panics
+    ///
+    /// If `every` is not positive or `until_time` is not finite.
+    pub fn run_until_with_progress(
+        &mut self,
+        until_time: T,
+        every: T,
+        mut cb: impl FnMut(f64),
+    ) -> RunOutcome<T> {
+        assert!(
+            every > T::ZERO,
+            "progress interval must be positive, got {every:?}"
+        );
+        assert!(
+            until_time.is_finite(),
+            "progress needs a finite horizon, got {until_time:?}"
+        );
+        let start_time = self.state.now;
+        let start = self.processed;
+        let Some(limit) = self.start_run(until_time) else {
+            return self.outcome(start);
+        };
+        let span = (until_time - start_time).to_f64();
+        let every = every.to_f64();
+        let mut reported = 0.0;
+        let mut report = |to: T| {
+            let crossed = ((to - start_time).to_f64() / every).floor();
+            while reported < crossed {
+                reported += 1.0;
+                cb((reported * every / span).min(1.0));
+            }
+        };
+        while let Some(scheduled) = self.state.pop_due(limit) {
+            report(scheduled.at);
+            self.execute(scheduled);
+            if let Some(reason) = self.interruption() {
+                return self.interrupted(start, reason);
+            }
+        }
+        self.finish(until_time);
+        report(until_time);
+        if span == 0.0 || reported * every < span {
+            cb(1.0);
+        }
+        self.outcome(start)
+    }
+
+    /// Like `run_until`, but events at exactly `until_time` stay pending.
+    ///
+    /// The run stops before the first event with `time + epsilon >= until_time` (see
+    /// `set_horizon_epsilon`) and the clock is then advanced to `until_time`, so an
+    /// event on the edge runs first in the next call. Consecutive calls thus split a
+    /// simulation into half-open windows `[start, until_time)` in which every event
+    /// runs exactly once.
+    pub fn run_until_exclusive(&mut self, until_time: T) -> RunOutcome<T> {
+        let start = self.processed;
+        if self.start_run(until_time).is_none() {
+            return self.outcome(start);
+        }
+        while let Some(at) = self.state.queue.peek_min().map(|s| s.at) {
+            if at + self.horizon_epsilon >= until_time {
+                break;
+            }
+            // Pop only the head's instant: if a guard drops it, the next event may lie
+            // on or past the edge.
+            if let Some(scheduled) = self.state.pop_due(at) {
+                self.execute(scheduled);
+                if let Some(reason) = self.interruption() {
+                    return self.interrupted(start, reason);
+                }
+            }
+        }
+        self.finish(until_time);
+        self.outcome(start)
+    }
+
+    /// Execute at most `max_events` of the events due by `horizon`, for driving a
+    /// simulation in bounded slices (e.g. one per frame of a UI loop).
+    ///
+    /// A slice that uses up its budget leaves the clock at the last executed event
+    /// and reports `StopReason::EventBudget`; only the slice that runs out of due
+    /// events advances the clock to `horizon`, like `run_until`. Slicing a run thus
+    /// ends in the same state as one `run_until(horizon)`, without the clock jumping
+    /// ahead between slices.
+    pub fn run_slice(&mut self, max_events: usize, horizon: T) -> RunOutcome<T> {
+        let start = self.processed;
+        let Some(limit) = self.start_run(horizon) else {
+            return self.outcome(start);
+        };
+        while self.processed - start < max_events {
+            let Some(scheduled) = self.state.pop_due(limit) else {
+                self.finish(horizon);
+                return self.outcome(start);
+            };
+            self.execute(scheduled);
+            if let Some(reason) = self.interruption() {
+                return self.interrupted(start, reason);
+            }
+        }
+        self.flush_snapshot();
+        let mut outcome = self.outcome(start);
+        if self
+            .state
+            .queue
+            .peek_min()
+            .is_some_and(|next| next.at <= limit)
+        {
+            outcome.reason = StopReason::EventBudget;
+        } else {
+            // The budget ran out with the last due event: the run is complete.
+            self.finish(horizon);
+            outcome = self.outcome(start);
+        }
+        outcome
+    }
+
+    /// Run until the queue is empty, with no horizon.
+    ///
+    /// The clock stays at the last executed event's time, which is also the time of
+    /// the final history snapshot. A model that keeps rescheduling itself never returns.
+    /// Like `run_until`, it stops early after an event that failed under
+    /// `ErrorPolicy::Abort` or overran the zero-delay chain limit.
+    pub fn run(&mut self) {
+        while self.step().is_some() && self.interruption().is_none() {}
+        self.flush_snapshot();
+    }
+
+    /// Execute exactly the next pending event and return the time it fired at, or
+    /// `None` if the queue is empty.
+    ///
+    /// History and the event log are updated as for one iteration of `run_until`, but
+    /// the clock stays at the event's time instead of moving to a horizon.
+    pub fn step(&mut self) -> Option<T> {
+        self.state.horizon = None;
+        let scheduled = self.state.pop_due(T::MAX)?;
+        let at = scheduled.at;
+        self.execute(scheduled);
+        Some(at)
+    }
+
+    /// Execute up to `n` events, fewer if the queue drains first, and return how many ran.
+    ///
+    /// Bookkeeping matches `run_until`, but the clock is left at the last event's time.
+    /// Stops early for the same reasons as `run`.
+    pub fn run_n_events(&mut self, n: usize) -> usize {
+        let mut executed = 0;
+        while executed < n && self.step().is_some() {
+            executed += 1;
+            if self.interruption().is_some() {
+                break;
+            }
+        }
+        self.flush_snapshot();
+        executed
+    }
+
+    /// Make `until_time` the horizon events see, and return `due_limit(until_time)`.
+    fn start_run(&mut self, until_time: T) -> Option<T> {
+        let limit = self.due_limit(until_time);
+        self.state.horizon = Some(until_time);
+        limit
+    }
+
+    /// Latest event time a run to `until_time` may execute, or `None` if the horizon
+    /// lies behind the clock and the run is a no-op.
+    fn due_limit(&self, until_time: T) -> Option<T> {
+        // Only NaN-like values are unordered with themselves.
+        assert!(
+            until_time.partial_cmp(&until_time).is_some(),
+            "run horizon must not be NaN"
+        );
+        (until_time >= self.state.now).then_some(until_time + self.horizon_epsilon)
+    }
+
+    /// Run until the first event matching `pred` has executed, or until `run_until`
+    /// would stop.
+    ///
+    /// On a match the engine stops right after that event, leaving the clock at its
+    /// time rather than fast-forwarding to `until_time`.
+    pub fn run_until_event(&mut self, until_time: T, pred: impl Fn(&E) -> bool) -> StopReason {
+        let Some(limit) = self.start_run(until_time) else {
+            return self.exhausted_reason();
+        };
+        while let Some(scheduled) = self.state.pop_due(limit) {
+            let matched = pred(&scheduled.event);
+            self.execute(scheduled);
+            if matched {
+                self.flush_snapshot();
+                return StopReason::MatchedEvent;
+            }
+            if let Some(reason) = self.interruption() {
+                self.flush_snapshot();
+                return reason;
+            }
+        }
+        self.finish(until_time);
+        self.exhausted_reason()
+    }
+
+    /// Run until `stop` returns `true` for the state after an event, or until
+    /// `run_until` would stop.
+    ///
+    /// The condition is checked after each executed event; when it fires the clock is
+    /// left at that event's time.
+    pub fn run_until_with(
+        &mut self,
+        until_time: T,
+        mut stop: impl FnMut(&S) -> bool,
+    ) -> StopReason {
+        let Some(limit) = self.start_run(until_time) else {
+            return self.exhausted_reason();
+        };
+        while let Some(scheduled) = self.state.pop_due(limit) {
+            self.execute(scheduled);
+            if stop(&self.state.data) {
+                self.flush_snapshot();
+                return StopReason::Predicate;
+            }
+            if let Some(reason) = self.interruption() {
+                self.flush_snapshot();
+                return reason;
+            }
+        }
+        self.finish(until_time);
+        self.exhausted_reason()
+    }
+
+    /// Like `run_until`, but call `on_step` once per distinct timestamp, after every event
+    /// at that instant (including ones scheduled during it) has executed.
+    ///
+    /// This yields one consistent frame per instant, e.g. for rendering. A run stopped
+    /// mid-instant (see `StopReason::EventFailed`) skips that instant's frame.
+    pub fn run_by_time_step(
+        &mut self,
+        until_time: T,
+        mut on_step: impl FnMut(T, &S),
+    ) -> RunOutcome<T> {
+        let start = self.processed;
+        let Some(limit) = self.start_run(until_time) else {
+            return self.outcome(start);
+        };
+        while let Some(scheduled) = self.state.pop_due(limit) {
+            let at = scheduled.at;
+            self.execute(scheduled);
+            if let Some(reason) = self.interruption() {
+                return self.interrupted(start, reason);
+            }
+            if self.state.queue.peek_min().map(|next| next.at) != Some(at) {
+                on_step(at, &self.state.data);
+            }
+        }
+        self.finish(until_time);
+        self.outcome(start)
+    }
+
+    /// Run until `until_time`, merging events from an external `source` with the queue.
+    ///
+    /// Source items are scheduled lazily, each once it is due no later than the queue
+    /// head, so a long or endless source is never held in memory. They then go through
+    /// the same checks as `State::schedule_result` (rejected times, coalescing, the
+    /// `on_schedule` hook, jitter) and are ordered like any other event, losing ties to
+    /// events queued before them. `source` should yield events in non-decreasing
+    /// `Event::time()` order; an item earlier than the clock when it is read is skipped
+    /// and counted in `past_schedules`. The first source item beyond the horizon is moved
+    /// into the queue so it is not lost; later items are left in the iterator (pass
+    /// `iter.by_ref()` to keep consuming it in a subsequent call). The same holds when
+    /// the run stops early like `run_until`.
+    pub fn run_with_source(
+        &mut self,
+        until_time: T,
+        source: impl Iterator<Item = E>,
+    ) -> RunOutcome<T> {
+        let start = self.processed;
+        let Some(limit) = self.start_run(until_time) else {
+            return self.outcome(start);
+        };
+        let mut source = source.fuse();
+        // The item read from the source but not due yet.
+        let mut next = None;
+        loop {
+            while let Some(event) = next.take().or_else(|| source.next()) {
+                let at = event.time();
+                if at > limit || self.state.queue.peek_min().is_some_and(|head| at > head.at) {
+                    next = Some(event);
+                    break;
+                }
+                let _ = self.state.schedule_external(event);
+            }
+            let Some(at) = self.state.queue.peek_min().map(|s| s.at) else {
+                break;
+            };
+            if at > limit {
+                break;
+            }
+            // Pop only the head's instant: if a guard drops it, the source may hold the
+            // next event.
+            let Some(scheduled) = self.state.pop_due(at) else {
+                continue;
+            };
+            self.execute(scheduled);
+            if let Some(reason) = self.interruption() {
+                // Keep the item already read from the source, as at the horizon.
+                if let Some(event) = next {
+                    let _ = self.state.schedule_external(event);
+                }
+                return self.interrupted(start, reason);
+            }
+        }
+        if let Some(event) = next {
+            let _ = self.state.schedule_external(event);
+        }
+        self.finish(until_time);
+        self.outcome(start)
+    }
+
+    /// Run to `until_time`, but stop early once the system reaches a steady state.
+    ///
+    /// After each event `extract` samples a metric from the user data; the run is
+    /// considered stable once the last `window` samples all lie within `tol` of each
+    /// other (max - min <= tol). Returns which condition ended the run: `Stable` leaves
+    /// the clock at the event that settled the metric, otherwise this behaves like
+    /// `run_until`.
+    ///
+    /// # Panics
+    ///
+    /// If `window` is 0.
+    pub fn run_until_or_stable(
+        &mut self,
+        until_time: T,
+        extract: impl Fn(&S) -> f64,
+        tol: f64,
+        window: usize,
+    ) -> StopReason {
+        assert!(window > 0, "stability window must hold at least one sample");
+        let Some(limit) = self.start_run(until_time) else {
+            return self.exhausted_reason();
+        };
+        let mut samples = VecDeque::with_capacity(window);
+        while let Some(scheduled) = self.state.pop_due(limit) {
+            self.execute(scheduled);
+            if samples.len() == window {
+                samples.pop_front();
+            }
+            samples.push_back(extract(&self.state.data));
+            if samples.len() == window {
+                let (lo, hi) = samples
+                    .iter()
+                    .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &x| {
+                        (lo.min(x), hi.max(x))
+                    });
+                if hi - lo <= tol {
+                    self.flush_snapshot();
+                    return StopReason::Stable;
+                }
+            }
+            if let Some(reason) = self.interruption() {
+                self.flush_snapshot();
+                return reason;
+            }
+        }
+        self.finish(until_time);
+        self.exhausted_reason()
+    }
+
+    /// Advance the clock to `scheduled.at`, execute it and record the bookkeeping.
+    fn execute(&mut self, scheduled: Scheduled<S, E, T>) {
+        debug_assert!(
+            scheduled.at >= self.state.now,
+            "simulation time would run backwards: event at {:?} popped at now = {:?}",
+            scheduled.at,
+            self.state.now
+        );
+        self.react_to_warmup();
+        self.integrate_trackers(scheduled.at);
+        self.cross_markers(scheduled.at);
+        if let Some(gaps) = &mut self.gaps {
+            if let Some(last) = gaps.last.replace(scheduled.at) {
+                // Coincident events give a zero gap, which lands in bucket 0.
+                let bucket = ((scheduled.at - last).to_f64() / gaps.width) as usize;
+                if gaps.counts.len() <= bucket {
+                    gaps.counts.resize(bucket + 1, 0);
+                }
+                gaps.counts[bucket] += 1;
+            }
+        }
+        if self.zero_delay_chain.0 == scheduled.at {
+            self.zero_delay_chain.1 += 1;
+        } else {
+            self.zero_delay_chain = (scheduled.at, 1);
+        }
+        self.state.now = scheduled.at;
+        // Log the event before execution
+        if let Some(describe) = self.describe {
+            self.events
+                .push((self.state.now, describe(&scheduled.event)));
+        }
+        if let Some(copy) = self.copy_event {
+            self.typed_events
+                .push((self.state.now, copy(&scheduled.event)));
+        }
+        let handle = EventHandle(scheduled.seq);
+        if self.track_causality {
+            let parent = scheduled.parent.map_or(ROOT_PARENT, EventHandle::seq);
+            self.causal.push((parent, scheduled.seq));
+        }
+        let label = scheduled.event.label();
+        *self.state.event_counts.entry(label).or_default() += 1;
+        if let Some(before) = &mut self.before {
+            before(self.state.now, &self.state.data);
+        }
+        let started = self.profile.is_some().then(Instant::now);
+        self.state.current = Some(handle);
+        self.state.current_label = Some(label);
+        #[cfg(feature = "tracing")]
+        let span = tracing::trace_span!("event", label = %label, at = ?self.state.now).entered();
+        let errors = self.state.errors.len();
+        scheduled.event.execute(&mut self.state);
+        self.failed = self.state.errors.len() > errors;
+        #[cfg(feature = "tracing")]
+        drop(span);
+        self.state.current = None;
+        self.state.current_label = None;
+        if let (Some(profile), Some(started)) = (&mut self.profile, started) {
+            let elapsed = started.elapsed();
+            let entry = profile.entry(label).or_default();
+            entry.total += elapsed;
+            entry.max = entry.max.max(elapsed);
+            entry.count += 1;
+        }
+        self.processed += 1;
+        if let Some(after) = &mut self.after {
+            after(self.state.now, &self.state.data);
+        }
+        self.record_snapshot();
+    }
+
+    /// Fire the time markers at or before `to`, which the clock is about to reach.
+    fn cross_markers(&mut self, to: T) {
+        if self.markers.first().is_none_or(|(t, _)| *t > to) {
+            return;
+        }
+        let due = self.markers.partition_point(|(t, _)| *t <= to);
+        for (_, mut cb) in self.markers.drain(..due) {
+            cb(&self.state.data);
+        }
+    }
+
+    /// Fast-forward the clock to the horizon and record the closing snapshot.
+    fn finish(&mut self, until_time: T) {
+        // An infinite horizon means "run to completion": the queue is drained and the
+        // clock stays at the last event.
+        let until_time = if until_time.is_finite() {
+            until_time
+        } else {
+            self.state.now
+        };
+        self.state.integrate_queue(until_time);
+        self.react_to_warmup();
+        self.integrate_trackers(until_time);
+        self.cross_markers(until_time);
+        if self.state.now < until_time {
+            self.state.now = until_time;
+        }
+        if self.unsampled || self.history.last().map(|s| s.0) != Some(self.state.now) {
+            self.store_snapshot();
+        }
+    }
+
+    /// Restart the tracked integrals, and trim the logs if trimming is on, when the
+    /// clock just crossed the warm-up time.
+    fn react_to_warmup(&mut self) {
+        if !std::mem::take(&mut self.state.warmup_reached) {
+            return;
+        }
+        for tracker in &mut self.trackers {
+            tracker.area = 0.0;
+            tracker.since = self.state.stats_start;
+        }
+        if !self.trim_at_warmup {
+            return;
+        }
+        self.history.clear();
+        self.history_points.clear();
+        self.unsampled = false;
+        self.events.clear();
+        self.events_from = self.processed;
+        self.typed_events.clear();
+        self.typed_from = self.processed;
+    }
+
+    /// Integrate every tracked projection over the dwell time from `now` to `to`, during
+    /// which the user data is unchanged.
+    fn integrate_trackers(&mut self, to: T) {
+        for tracker in &mut self.trackers {
+            let from = if tracker.since > self.state.now {
+                tracker.since
+            } else {
+                self.state.now
+            };
+            if to > from {
+                tracker.area += (tracker.project)(&self.state.data) * (to - from).to_f64();
+            }
+        }
+    }
+
+    /// Why a run that ran out of due events stopped.
+    fn exhausted_reason(&self) -> StopReason {
+        if self.state.queue.is_empty() {
+            StopReason::QueueEmpty
+        } else {
+            StopReason::HorizonReached
+        }
+    }
+
+    /// Why the run must stop after the event just executed, if it must: the event
+    /// failed under `ErrorPolicy::Abort`, or overran the zero-delay chain limit.
+    fn interruption(&self) -> Option<StopReason> {
+        if self.failed && self.error_policy == ErrorPolicy::Abort {
+            Some(StopReason::EventFailed)
+        } else if self
+            .max_zero_delay_chain
+            .is_some_and(|max| self.zero_delay_chain.1 > max)
+        {
+            Some(StopReason::ZeroDelayLoop)
+        } else {
+            None
+        }
+    }
+
+    /// Outcome of a run that started with `start` events processed and was stopped
+    /// early for `reason`.
+    fn interrupted(&mut self, start: usize, reason: StopReason) -> RunOutcome<T> {
+        self.flush_snapshot();
+        RunOutcome {
+            reason,
+            ..self.outcome(start)
+        }
+    }
+
+    /// Outcome of a horizon-bounded run that started with `start` events processed.
+    fn outcome(&self, start: usize) -> RunOutcome<T> {
+        RunOutcome {
+            stopped_at: self.state.now,
+            reason: self.exhausted_reason(),
+            events_executed: self.processed - start,
+        }
+    }
+
+    /// Snapshot the state after an event, unless sampling says it is too soon.
+    fn record_snapshot(&mut self) {
+        if let (Some(dt), Some(last)) = (self.sample_interval, self.history.last()) {
+            if self.state.now - last.0 < dt {
+                self.unsampled = true;
+                return;
+            }
+        }
+        self.store_snapshot();
+    }
+
+    /// Record the final state of a run if sampling skipped it.
+    fn flush_snapshot(&mut self) {
+        if self.unsampled {
+            self.store_snapshot();
+        }
+    }
+
+    fn store_snapshot(&mut self) {
+        self.unsampled = false;
+        let Some(snapshot) = self.snapshot else {
+            return;
+        };
+        if self.history_capacity == Some(0) {
+            return;
+        }
+        if let (Some(eq), Some(last)) = (self.skip_unchanged, self.history.last()) {
+            if eq(&last.1, &self.state.data) {
+                return;
+            }
+        }
+        self.history
+            .push((self.state.now, snapshot(&self.state.data)));
+        self.history_points.push(HistoryPoint {
+            processed: self.processed,
+            metrics: self.state.metrics.clone(),
+            state: self.restore.map(|restore| Box::new(restore(&self.state))),
+        });
+        // Evict in batches so the retained window stays a contiguous slice without
+        // shifting the buffer on every push.
+        if let Some(capacity) = self.history_capacity {
+            if self.history.len() >= capacity.saturating_mul(2) {
+                let excess = self.history.len() - capacity;
+                self.history.drain(..excess);
+                self.history_points.drain(..excess);
+            }
+        }
+    }
+
+    /// Index of the oldest snapshot inside the retained history window.
+    fn history_start(&self) -> usize {
+        self.history_capacity
+            .map_or(0, |capacity| self.history.len().saturating_sub(capacity))
+    }
+
+    /// Undo the last `steps` executed events, restoring state, clock and pending queue.
+    ///
+    /// History and the event log are truncated to the point right after the last
+    /// retained event, so running again continues as if the undone events never ran.
+    /// Returns `false` (and changes nothing) if fewer than `steps` events were executed
+    /// or no restore point was taken there (see `set_rewindable`).
+    pub fn rewind(&mut self, steps: usize) -> bool {
+        let Some(target) = self.processed.checked_sub(steps) else {
+            return false;
+        };
+        let start = self.history_start();
+        self.history_points[start..]
+            .iter()
+            .position(|point| point.processed == target)
+            .is_some_and(|index| self.rewind_to(index).is_ok())
+    }
+
+    /// Return to the snapshot `history()[index]`: state, clock and pending queue are
+    /// restored, and history and the event logs are truncated after it, so running again
+    /// continues from there, e.g. to explore a what-if after changing the state.
+    ///
+    /// Only snapshots taken while the engine is rewindable carry the full state needed;
+    /// see `set_rewindable` for that mode and its cost. Time markers already fired and
+    /// tracked integrals are not rolled back.
+    pub fn rewind_to(&mut self, index: usize) -> Result<(), RewindError> {
+        let start = self.history_start();
+        let index = start + index;
+        let point = self
+            .history_points
+            .get(index)
+            .ok_or(RewindError::OutOfRange)?;
+        let (Some(restore), Some(point)) = (self.restore, &point.state) else {
+            return Err(RewindError::NotRewindable);
+        };
+        let target = self.history_points[index].processed;
+        let rules = self.state.rules.clone();
+        self.state = restore(point);
+        self.state.rules = rules;
+        self.history.truncate(index + 1);
+        self.history_points.truncate(index + 1);
+        // Snapshots already evicted from the window must not resurface.
+        self.history.drain(..start);
+        self.history_points.drain(..start);
+        self.events
+            .truncate(target.saturating_sub(self.events_from));
+        self.typed_events
+            .truncate(target.saturating_sub(self.typed_from));
+        self.causal
+            .truncate(target.saturating_sub(self.causal_from));
+        self.processed = target;
+        self.unsampled = false;
+        Ok(())
+    }
+
+    /// Turn causality tracking (see `causality`) on or off. Off by default, since it
+    /// records an entry for every executed event. Disabling discards the entries
+    /// gathered so far.
+    pub fn set_track_causality(&mut self, enabled: bool) {
+        self.causal.clear();
+        self.causal_from = self.processed;
+        self.track_causality = enabled;
+    }
+
+    /// Causality edges of the events executed while causality tracking is on, in
+    /// execution order: `(parent, child)` sequence numbers (see `EventHandle::seq`),
+    /// where the parent is the event whose `execute` scheduled the child, or
+    /// `ROOT_PARENT` for events scheduled from outside a run. Together the edges form
+    /// a causal tree.
+    pub fn causality(&self) -> &[(u64, u64)] {
+        &self.causal
+    }
+
+    /// The edges of `causality` as handles, child first: each executed event paired
+    /// with the event that scheduled it, `None` for roots.
+    pub fn causal_tree(&self) -> Vec<(EventHandle, Option<EventHandle>)> {
+        self.causal
+            .iter()
+            .map(|&(parent, child)| {
+                let parent = (parent != ROOT_PARENT).then_some(EventHandle(parent));
+                (EventHandle(child), parent)
+            })
+            .collect()
+    }
+
+    /// Number of events executed so far.
+    pub fn events_processed(&self) -> usize {
+        self.processed
+    }
+
+    /// Attach a piece of metadata, replacing any earlier value of the same type.
+    ///
+    /// The engine never reads it; it lets scenario parameters travel with the results.
+    pub fn set_meta<M: Any>(&mut self, value: M) {
+        self.meta.insert(TypeId::of::<M>(), Box::new(value));
+    }
+
+    /// The metadata of type `M` attached with `set_meta`, if any.
+    pub fn get_meta<M: Any>(&self) -> Option<&M> {
+        self.meta.get(&TypeId::of::<M>())?.downcast_ref()
+    }
+
+    /// Executed events with the time they fired at, in execution order, recorded
+    /// while the typed log is on. Unlike `events`, analysis code can match on variants.
+    pub fn events_typed(&self) -> &[(T, E)] {
+        &self.typed_events
+    }
+
+    /// Turn per-label wall-clock profiling of `execute` calls on or off.
+    ///
+    /// Disabling discards the timings gathered so far.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = enabled.then(HashMap::new);
+    }
+
+    /// Wall-clock cost of each event label as `(label, total, max, count)`, most
+    /// expensive first. Empty unless profiling is enabled.
+    pub fn profile_report(&self) -> Vec<(String, std::time::Duration, std::time::Duration, u64)> {
+        let mut report: Vec<_> = self
+            .profile
+            .iter()
+            .flatten()
+            .map(|(label, e)| (label.to_string(), e.total, e.max, e.count))
+            .collect();
+        report.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        report
+    }
+
+    /// Number of executed events per `Event::label`, a cheap profile of where the
+    /// simulation spends its events. Rewinding restores the counts with the state.
+    pub fn event_counts(&self) -> &HashMap<&'static str, u64> {
+        &self.state.event_counts
+    }
+
+    /// Time-weighted average number of pending events since the run started, or since
+    /// the warm-up time once it has been reached.
+    ///
+    /// Each queue length is weighted by how long it lasted (Little's-law style), rather
+    /// than averaged over events. Returns 0 before any simulated time has passed.
+    pub fn mean_queue_length(&self) -> f64 {
+        let elapsed = (self.state.now - self.state.stats_start).to_f64();
+        if elapsed > 0.0 {
+            self.state.queue_area / elapsed
+        } else {
+            0.0
+        }
+    }
+
+    /// Integrate `f` of the user data over simulated time under `name`, from now on.
+    ///
+    /// Each value is weighted by how long the data kept it (the dwell time between
+    /// events), not by the number of events. Registering a name again replaces it.
+    /// `rewind` does not undo the accumulated integrals.
+    pub fn track(&mut self, name: &str, f: impl Fn(&S) -> f64 + 'static) {
+        self.trackers.retain(|tracker| tracker.name != name);
+        self.trackers.push(Tracker {
+            name: name.to_string(),
+            project: Box::new(f),
+            area: 0.0,
+            since: self.state.now,
+        });
+    }
+
+    /// Time-weighted average of the projection tracked under `name`, or `None` if no
+    /// such projection is registered. Before any time has passed this is its current
+    /// value. Restarts at the warm-up time, like the other statistics.
+    pub fn time_average(&self, name: &str) -> Option<f64> {
+        let tracker = self.trackers.iter().find(|tracker| tracker.name == name)?;
+        let elapsed = (self.state.now - tracker.since).to_f64();
+        Some(if elapsed > 0.0 {
+            tracker.area / elapsed
+        } else {
+            (tracker.project)(&self.state.data)
+        })
+    }
+
+    /// External counterpart of `State::peek_next_time`.
+    pub fn peek_next_time(&self) -> Option<T> {
+        self.state.peek_next_time()
+    }
+
+    /// External counterpart of `State::pending_len`.
+    pub fn pending_len(&self) -> usize {
+        self.state.pending_len()
+    }
+
+    /// External counterpart of `State::is_empty`.
+    pub fn is_empty(&self) -> bool {
+        self.state.is_empty()
+    }
+
+    /// Snapshot of the pending events in firing order, without consuming them.
+    pub fn pending_by_time(&self) -> Vec<(T, &E)> {
+        let mut pending: Vec<&Scheduled<S, E, T>> = self.state.queue.iter().collect();
+        // `Scheduled` orders latest-first for the max-heap, so sort descending.
+        pending.sort_by(|a, b| b.cmp(a));
+        pending.into_iter().map(|s| (s.at, &s.event)).collect()
+    }
+
+    /// Recorded `(time, user data)` snapshots, oldest first.
+    pub fn history(&self) -> &[(T, S)] {
+        &self.history[self.history_start()..]
+    }
+
+    /// Metrics recorded with the snapshot `history()[index]`, if it exists.
+    pub fn history_metrics(&self, index: usize) -> Option<&Metrics> {
+        self.history_points
+            .get(self.history_start() + index)
+            .map(|point| &point.metrics)
+    }
+
+    /// The snapshots of `history` as `(time, user data)` pairs, oldest first.
+    pub fn history_iter(&self) -> impl Iterator<Item = (T, &S)> {
+        self.history().iter().map(|(at, data)| (*at, data))
+    }
+
+    /// The user data as of time `t`: the latest snapshot taken at or before `t`, or
+    /// `None` if every retained snapshot is later.
+    ///
+    /// Of several snapshots at `t` the last one is returned, i.e. the state after every
+    /// event at that instant. History is ordered by time, so this is a binary search.
+    pub fn history_at(&self, t: T) -> Option<&S> {
+        let history = self.history();
+        let after = history.partition_point(|(at, _)| *at <= t);
+        after.checked_sub(1).map(|i| &history[i].1)
+    }
+
+    /// Record a snapshot only once `now` has advanced at least `dt` past the last
+    /// recorded one, instead of after every event, giving evenly spaced rows.
+    ///
+    /// The state at the end of a run is always recorded.
+    ///
+    /// # Panics
+    ///
+    /// If `dt` is negative or not finite.
+    pub fn with_sample_interval(mut self, dt: T) -> Self {
+        assert!(
+            dt >= T::ZERO && dt.is_finite(),
+            "sample interval must be non-negative and finite, got {dt:?}"
+        );
+        self.sample_interval = Some(dt);
+        self
+    }
+
+    /// Retain only the `capacity` most recent snapshots, evicting the oldest as new ones
+    /// arrive. The initial snapshot counts toward the limit, and `0` disables history.
+    ///
+    /// `rewind` can only go back as far as the retained window reaches.
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history_capacity = Some(capacity);
+        let start = self.history_start();
+        self.history.drain(..start);
+        self.history_points.drain(..start);
+        self
+    }
+
+    /// Count the simulated time between consecutive executed events in buckets of
+    /// `bucket_width` (see `gap_histogram`). Gaps between events already executed are
+    /// not counted. The histogram has one entry per bucket up to the largest gap, so
+    /// pick a width on the scale of the gaps you expect.
+    ///
+    /// # Panics
+    ///
+    /// If `bucket_width` is not positive and finite.
+    pub fn with_gap_histogram(mut self, bucket_width: f64) -> Self {
+        assert!(
+            bucket_width > 0.0 && bucket_width.is_finite(),
+            "bucket width must be positive and finite, got {bucket_width}"
+        );
+        self.gaps = Some(GapHistogram {
+            width: bucket_width,
+            counts: Vec::new(),
+            last: None,
+        });
+        self
+    }
+
+    /// Inter-event gap counts: entry `i` counts gaps in
+    /// `[i * bucket_width, (i + 1) * bucket_width)`, so coincident events fall in
+    /// bucket 0. Empty unless enabled with `with_gap_histogram`.
+    pub fn gap_histogram(&self) -> &[u64] {
+        self.gaps.as_ref().map_or(&[], |gaps| &gaps.counts)
+    }
+
+    /// Recorded snapshots collapsed to one per distinct timestamp.
+    ///
+    /// When several events fire at the same instant only the state after the last of
+    /// them is kept, which is usually the series you want to plot.
+    pub fn instant_history(&self) -> Vec<(T, &S)> {
+        let mut out: Vec<(T, &S)> = Vec::new();
+        for (now, data) in self.history_iter() {
+            match out.last_mut() {
+                Some(last) if last.0 == now => last.1 = data,
+                _ => out.push((now, data)),
+            }
+        }
+        out
+    }
+}
+
+impl<S, E: Event<S, T, C> + std::fmt::Debug, T: SimTime, C> Engine<S, E, T, C> {
+    /// Turn the string event log (see `events`) on or off.
+    ///
+    /// Each executed event is formatted with `Debug`. Off by default, so engines whose
+    /// events do not implement `Debug` work too. Disabling discards the log.
+    pub fn set_event_log(&mut self, enabled: bool) {
+        self.events.clear();
+        if enabled {
+            self.events.reserve(self.expected_events);
+        }
+        self.events_from = self.processed;
+        self.describe = enabled.then_some(|event: &E| format!("{event:?}"));
+    }
+
+    /// Access the chronological event log, recorded while the string log is on.
+    pub fn events(&self) -> &[(T, String)] {
+        &self.events
+    }
+}
+
+impl<S: PartialEq, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// Skip history snapshots whose user data equals the previous snapshot's.
+    ///
+    /// Events that leave the data untouched then leave no trace in `history`, so the
+    /// exact time of such no-op events is lost, as is the closing snapshot at the
+    /// horizon when nothing changed. `rewind` can only return to a point that still has
+    /// a snapshot.
+    pub fn set_skip_unchanged_snapshots(&mut self, enabled: bool) {
+        self.skip_unchanged = enabled.then_some(<S as PartialEq>::eq as fn(&S, &S) -> bool);
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<S: serde::Serialize, E: Event<S, T, C>, T: SimTime + serde::Serialize, C> Engine<S, E, T, C> {
+    /// Current time and user data as a JSON value: `{ "now": ..., "state": ... }`.
+    ///
+    /// Lets generic tooling (REPLs, dashboards) inspect any serializable model without
+    /// knowing its concrete type. Requires the `serde_json` feature.
+    pub fn state_json(&self) -> serde_json::Result<serde_json::Value> {
+        Ok(serde_json::json!({
+            "now": self.state.now,
+            "state": serde_json::to_value(&self.state.data)?,
+        }))
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl<S, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// The string event log (see `events`) as a JSON array of
+    /// `{ "time": number, "label": string }` objects, e.g. for web-based trace viewers.
+    /// Requires the `serde_json` feature.
+    pub fn events_to_json(&self) -> String {
+        let entries: Vec<_> = self
+            .events
+            .iter()
+            .map(|(at, label)| serde_json::json!({ "time": at.to_f64(), "label": label }))
+            .collect();
+        serde_json::Value::Array(entries).to_string()
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime + Sync, C> Engine<S, E, T, C> {
+    /// Run `n` independent replications of a model to `horizon` and return the value
+    /// `collect` reads from each one's final user data, in replication order.
+    ///
+    /// `make` builds replication `i`'s engine, typically with `Engine::new_seeded` and
+    /// a seed derived from `i`, plus its initial events. Each engine lives on a single
+    /// thread, so models need not be `Send`. With the `rayon` feature the replications
+    /// run in parallel; otherwise they run one after another. Either way the results
+    /// are the same.
+    pub fn run_replications(
+        n: usize,
+        make: impl Fn(usize) -> Self + Sync,
+        horizon: T,
+        collect: impl Fn(&S) -> f64 + Sync,
+    ) -> Vec<f64> {
+        let replicate = |i| {
+            let mut engine = make(i);
+            engine.run_until(horizon);
+            collect(&engine.state.data)
+        };
+        #[cfg(feature = "rayon")]
+        {
+            use rayon::prelude::*;
+            (0..n).into_par_iter().map(replicate).collect()
+        }
+        #[cfg(not(feature = "rayon"))]
+        {
+            (0..n).map(replicate).collect()
+        }
+    }
+}
+
+/// Concatenate history chunks from consecutive runs into one continuous series.
+///
+/// When a run is split across restarts, each chunk usually begins with the snapshot the
+/// previous one ended on. A chunk's first snapshot is dropped if it has the same `now`
+/// and data as the last snapshot already merged, so boundaries are not duplicated.
+pub fn merge_history<S: Clone + PartialEq, T: SimTime>(chunks: &[&[(T, S)]]) -> Vec<(T, S)> {
+    let mut merged: Vec<(T, S)> = Vec::with_capacity(chunks.iter().map(|c| c.len()).sum());
+    for chunk in chunks {
+        let skip = match (merged.last(), chunk.first()) {
+            (Some(last), Some(first)) => last.0 == first.0 && last.1 == first.1,
+            _ => false,
+        };
+        merged.extend_from_slice(&chunk[usize::from(skip)..]);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[derive(Default, Clone, Debug, PartialEq)]
+    struct Counter {
+        ticks: u32,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Tick {
+        at: Timestamp,
+        left: u32,
+    }
+
+    impl Event<Counter> for Tick {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn label(&self) -> &'static str {
+            "tick"
+        }
+        fn execute(self, state: &mut State<Counter, Tick>) {
+            state.state_mut().ticks += 1;
+            if self.left > 0 {
+                let next_at = self.at + 0.5;
+                state.schedule(Tick {
+                    at: next_at,
+                    left: self.left - 1,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn counter_advances() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.run_until(10.0);
+        assert_eq!(engine.state().ticks, 4);
+        assert!(engine.now() >= 10.0);
+    }
+
+    #[test]
+    fn event_counts_group_by_label() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_rewindable(true);
+        engine.schedule(Tick { at: 0.0, left: 5 });
+        engine.run_until(10.0);
+        assert_eq!(engine.event_counts().len(), 1);
+        assert_eq!(engine.event_counts()["tick"], engine.state().ticks as u64);
+        assert!(engine.rewind(2));
+        assert_eq!(engine.event_counts()["tick"], engine.state().ticks as u64);
+    }
+
+    #[test]
+    fn replaying_the_typed_log_reproduces_the_run() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_typed_log(true);
+        engine.schedule(Tick { at: 0.0, left: 6 });
+        engine.run();
+        let replayed = Engine::replay(Counter::default(), engine.events_typed());
+        assert_eq!(replayed.state(), engine.state());
+        assert_eq!(replayed.now(), engine.now());
+        assert!(replayed.is_empty());
+    }
+
+    #[test]
+    fn history_records_time_and_data_after_each_event() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 1.0, left: 3 });
+        let mut observed = vec![(engine.now(), engine.state().clone())];
+        while engine.step().is_some() {
+            observed.push((engine.now(), engine.state().clone()));
+        }
+        assert_eq!(engine.history(), &observed[..]);
+        assert_eq!(engine.history().len(), 5);
+    }
+
+    #[test]
+    fn reserved_queue_capacity_is_a_hint() {
+        let mut engine = Engine::<Counter, Tick>::with_queue_capacity(Counter::default(), 1000);
+        if let Queue::Heap(heap) = &engine.state.queue {
+            assert!(heap.capacity() >= 1000);
+        }
+        for i in 0..1000 {
+            engine.schedule(Tick {
+                at: f64::from(i) * 0.01,
+                left: 0,
+            });
+        }
+        engine.state.reserve(10);
+        engine.schedule(Tick { at: 20.0, left: 0 });
+        engine.run_until(30.0);
+        assert_eq!(engine.state().ticks, 1001);
+    }
+
+    #[test]
+    fn pending_by_time_is_sorted_and_read_only() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        for at in [3.0, 1.0, 2.0] {
+            engine.schedule(Tick { at, left: 0 });
+        }
+        let times: Vec<Timestamp> = engine.pending_by_time().iter().map(|(t, _)| *t).collect();
+        assert_eq!(times, vec![1.0, 2.0, 3.0]);
+        assert_eq!(engine.state.queue.len(), 3);
+        engine.run_until(10.0);
+        assert_eq!(engine.state().ticks, 3);
+    }
+
+    #[test]
+    fn dry_run_count_matches_real_run() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 30 });
+        let predicted = engine.dry_run_count(7.0);
+        assert_eq!(engine.state().ticks, 0);
+        assert_eq!(engine.history().len(), 1);
+        engine.run_until(7.0);
+        assert_eq!(engine.events_processed(), predicted);
+        assert_eq!(predicted, 15);
+    }
+
+    #[test]
+    fn dry_run_count_sees_the_horizon() {
+        // Work every time unit; the last shift before the horizon files a report.
+        #[derive(Clone)]
+        enum Shift {
+            Work(Timestamp),
+            Report(Timestamp),
+        }
+
+        impl Event<u32> for Shift {
+            fn time(&self) -> Timestamp {
+                match *self {
+                    Shift::Work(at) | Shift::Report(at) => at,
+                }
+            }
+            fn execute(self, state: &mut State<u32, Shift>) {
+                *state.state_mut() += 1;
+                let now = state.now();
+                if let Shift::Work(_) = self {
+                    if state.horizon().is_some_and(|h| now + 1.0 > h) {
+                        state.schedule(Shift::Report(now));
+                    } else {
+                        state.schedule(Shift::Work(now + 1.0));
+                    }
+                }
+            }
+        }
+
+        let mut engine = Engine::new(0);
+        engine.schedule(Shift::Work(0.0));
+        let predicted = engine.dry_run_count(10.0);
+        engine.run_until(10.0);
+        assert_eq!(predicted, 12);
+        assert_eq!(engine.events_processed(), predicted);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Probe {
+        Once {
+            at: Timestamp,
+        },
+        Chain {
+            at: Timestamp,
+            step: Timestamp,
+            left: u32,
+        },
+        Fanout {
+            at: Timestamp,
+        },
+    }
+
+    impl Event<Vec<(Timestamp, Timestamp)>> for Probe {
+        fn time(&self) -> Timestamp {
+            match *self {
+                Probe::Once { at } | Probe::Chain { at, .. } | Probe::Fanout { at } => at,
+            }
+        }
+        fn execute(self, state: &mut State<Vec<(Timestamp, Timestamp)>, Probe>) {
+            let (now, at) = (state.now(), self.time());
+            state.state_mut().push((now, at));
+            match self {
+                Probe::Once { .. } => {}
+                Probe::Chain { at, step, left } if left > 0 => state.schedule(Probe::Chain {
+                    at: at + step,
+                    step,
+                    left: left - 1,
+                }),
+                Probe::Chain { .. } => {}
+                Probe::Fanout { at } => {
+                    state.schedule(Probe::Once { at });
+                    state.schedule(Probe::Once { at: at + 0.1 });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn now_equals_event_time_inside_execute() {
+        let mut engine = Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new(Vec::new());
+        engine.schedule(Probe::Once { at: 0.0 });
+        engine.schedule(Probe::Chain {
+            at: 0.3,
+            step: 0.7,
+            left: 5,
+        });
+        engine.schedule(Probe::Fanout { at: 2.25 });
+        engine.run_until(10.0);
+        assert_eq!(engine.state().len(), 10);
+        for &(now, at) in engine.state() {
+            assert_eq!(now, at);
+        }
+    }
+
+    #[test]
+    fn merge_history_drops_shared_boundary() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.run_until(1.0);
+        let split = engine.history().len() - 1;
+        engine.run_until(4.0);
+        let history = engine.history();
+        let (first, second) = (&history[..=split], &history[split..]);
+        assert_eq!(first.last().unwrap().0, second[0].0);
+
+        let merged = merge_history(&[first, second]);
+        assert_eq!(merged.len(), history.len());
+        for (a, b) in merged.iter().zip(history) {
+            assert_eq!(a, b);
+        }
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn schedule_jitter_is_seeded_and_clamped() {
+        use rand::Rng;
+
+        fn fire_times(jitter: bool, start: Timestamp, cap: Option<Timestamp>) -> Vec<Timestamp> {
+            let mut engine =
+                Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new_at(Vec::new(), start);
+            engine.set_max_time(cap);
+            if jitter {
+                engine.set_schedule_jitter(|rng| rng.random_range(-0.5..0.5));
+            }
+            for at in [0.0, 1.0, 2.0, 3.0] {
+                engine.schedule(Probe::Once { at: start + at });
+            }
+            engine.run_until(start + 10.0);
+            engine.state().iter().map(|&(now, _)| now).collect()
+        }
+
+        assert_eq!(fire_times(false, 0.0, None), vec![0.0, 1.0, 2.0, 3.0]);
+        let jittered = fire_times(true, 0.0, None);
+        assert_eq!(jittered, fire_times(true, 0.0, None));
+        assert_ne!(jittered, fire_times(false, 0.0, None));
+        assert!(jittered.iter().all(|&t| t >= 0.0));
+
+        // Later starts and a time cap bound the jitter on both sides.
+        let jittered = fire_times(true, 100.0, Some(103.0));
+        assert_eq!(jittered.len(), 4);
+        assert!(jittered.iter().all(|&t| (100.0..=103.0).contains(&t)));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn same_seed_gives_identical_event_logs() {
+        use rand::Rng;
+
+        #[derive(Clone, Debug)]
+        struct Arrival {
+            at: Timestamp,
+        }
+
+        impl Event<u32> for Arrival {
+            fn time(&self) -> Timestamp {
+                self.at
+            }
+            fn execute(self, state: &mut State<u32, Arrival>) {
+                *state.state_mut() += 1;
+                let gap: f64 = state.rng().random_range(0.1..2.0);
+                let at = state.now() + gap;
+                state.schedule(Arrival { at });
+            }
+        }
+
+        fn log(seed: u64) -> Vec<(Timestamp, String)> {
+            let mut engine = Engine::new_seeded(0, seed);
+            engine.set_event_log(true);
+            engine.schedule(Arrival { at: 0.0 });
+            engine.run_until(50.0);
+            engine.events().to_vec()
+        }
+
+        assert_eq!(log(7), log(7));
+        assert_ne!(log(7), log(8));
+
+        // Restore points capture the generator, so a rewound run redraws the same delays.
+        let mut engine = Engine::new_seeded(0, 7);
+        engine.set_rewindable(true);
+        engine.schedule(Arrival { at: 0.0 });
+        engine.run_n_events(10);
+        let ahead = engine.peek_next_time();
+        assert!(engine.rewind(3));
+        engine.run_n_events(3);
+        assert_eq!(engine.peek_next_time(), ahead);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn exponential_delays_have_expected_mean() {
+        let mut state = State::<u32, Alarm>::new(0);
+        let rate = 4.0;
+        let n = 10_000;
+        let mean = (0..n).map(|_| state.sample_exponential(rate)).sum::<f64>() / n as f64;
+        // The standard error of the mean is (1 / rate) / sqrt(n) = 0.0025.
+        assert!((mean - 1.0 / rate).abs() < 0.01, "mean {mean}");
+
+        let outcome = state.schedule_exponential(rate, |at| Alarm { minute: at as u32 });
+        assert!(matches!(outcome, ScheduleOutcome::Scheduled(_)));
+        assert!(state.peek_next_time().unwrap() > 0.0);
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn replications_estimate_the_poisson_mean() {
+        #[derive(Clone)]
+        struct Arrival(Timestamp);
+
+        impl Event<u32> for Arrival {
+            fn time(&self) -> Timestamp {
+                self.0
+            }
+            fn execute(self, state: &mut State<u32, Arrival>) {
+                *state.state_mut() += 1;
+                state.schedule_exponential(2.0, Arrival);
+            }
+        }
+
+        let make = |i: usize| {
+            let mut engine = Engine::new_seeded(0, i as u64);
+            engine.schedule(Arrival(0.0));
+            engine
+        };
+        let counts = Engine::run_replications(50, make, 25.0, |&n| f64::from(n));
+        assert_eq!(counts.len(), 50);
+        assert_eq!(
+            counts,
+            Engine::run_replications(50, make, 25.0, |&n| f64::from(n))
+        );
+        // One seed arrival plus Poisson(2 * 25) more; the mean's standard error is 1.
+        let mean = counts.iter().sum::<f64>() / counts.len() as f64;
+        assert!((mean - 51.0).abs() < 4.0, "mean {mean}");
+        assert!(counts.iter().any(|&c| c != counts[0]));
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    #[should_panic(expected = "positive")]
+    fn exponential_rejects_non_positive_rate() {
+        State::<u32, Alarm>::new(0).sample_exponential(0.0);
+    }
+
+    #[test]
+    fn mean_queue_length_is_time_weighted() {
+        let mut engine = Engine::new(Vec::new());
+        assert_eq!(engine.mean_queue_length(), 0.0);
+        for (id, at) in [(1, 1.0), (2, 2.0), (3, 4.0)] {
+            engine.schedule(Keyed {
+                at,
+                priority: 0,
+                id,
+            });
+        }
+        engine.run_until(5.0);
+        // 3 pending over [0, 1), 2 over [1, 2), 1 over [2, 4) and none over [4, 5].
+        let expected = (3.0 + 2.0 + 2.0 * 1.0) / 5.0;
+        assert!((engine.mean_queue_length() - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn warmup_discards_startup_statistics() {
+        let mut engine = Engine::new(Vec::new());
+        engine.set_event_log(true);
+        engine.set_warmup(5.0);
+        engine.set_warmup_trims_logs(true);
+        for (id, at) in [(1, 1.0), (2, 2.0), (3, 3.0), (4, 6.0)] {
+            engine.schedule(Keyed {
+                at,
+                priority: 0,
+                id,
+            });
+        }
+        engine.run_until(10.0);
+        // Only the event at 6 was pending after the warm-up, over [5, 6).
+        assert!((engine.mean_queue_length() - 0.2).abs() < 1e-12);
+        assert_eq!(engine.event_counts()["event"], 1);
+        assert_eq!(engine.state(), &vec![1, 2, 3, 4]);
+        assert_eq!(engine.events().len(), 1);
+        assert!(engine.history().iter().all(|(now, _)| *now >= 5.0));
+    }
+
+    #[test]
+    fn time_average_weights_values_by_dwell_time() {
+        let mut engine = Engine::new(Vec::new());
+        engine.track(
+            "busy",
+            |ids: &Vec<u64>| if ids.is_empty() { 0.0 } else { 10.0 },
+        );
+        assert_eq!(engine.time_average("busy"), Some(0.0));
+        assert_eq!(engine.time_average("idle"), None);
+        engine.schedule(Keyed {
+            at: 5.0,
+            priority: 0,
+            id: 1,
+        });
+        engine.run_until(10.0);
+        assert!((engine.time_average("busy").unwrap() - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn guarded_events_are_dropped_when_the_guard_fails() {
+        let keyed = |id| Keyed {
+            at: 0.0,
+            priority: 0,
+            id,
+        };
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule_if(2.0, keyed(10), |ids: &Vec<u64>| ids.is_empty());
+        engine.schedule_if(3.0, keyed(20), |ids: &Vec<u64>| ids.len() < 5);
+        engine.schedule_at(1.0, keyed(1));
+        engine.run_until(5.0);
+        // Event 1 ran first, so the first guard no longer held at t=2.
+        assert_eq!(engine.state(), &vec![1, 20]);
+        assert_eq!(engine.events_processed(), 2);
+    }
+
+    /// Records the time it fired at; driven by `schedule_periodic` in the tests below.
+    #[derive(Clone)]
+    struct Beat(Timestamp);
+
+    impl Event<Vec<Timestamp>> for Beat {
+        fn time(&self) -> Timestamp {
+            self.0
+        }
+        fn execute(self, state: &mut State<Vec<Timestamp>, Beat>) {
+            let now = state.now();
+            state.state_mut().push(now);
+        }
+    }
+
+    #[test]
+    fn periodic_events_repeat_until_cancelled() {
+        let mut engine = Engine::new(Vec::new());
+        let ScheduleOutcome::Scheduled(handle) = engine.schedule_periodic(0.0, 2.0, Beat) else {
+            panic!("first beat was not scheduled");
+        };
+        // The horizon is inclusive, so the beat landing exactly on it fires too.
+        engine.run_until(10.0);
+        assert_eq!(engine.state(), &vec![0.0, 2.0, 4.0, 6.0, 8.0, 10.0]);
+        engine.cancel(handle);
+        engine.run_until(20.0);
+        assert_eq!(engine.state().len(), 6);
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn gap_histogram_buckets_inter_event_gaps() {
+        let mut engine = Engine::<u32, Alarm>::new(0).with_gap_histogram(1.0);
+        // `schedule_at` overrides the alarms' whole-minute times.
+        for at in [1.0, 1.0, 1.5, 3.0, 3.5] {
+            engine.schedule_at(at, Alarm { minute: 0 });
+        }
+        engine.run_until(5.0);
+        // Gaps: 0, 0.5, 1.5, 0.5.
+        assert_eq!(engine.gap_histogram(), &[3, 1]);
+        assert!(Engine::<u32, Alarm>::new(0).gap_histogram().is_empty());
+    }
+
+    #[test]
+    fn reset_engine_matches_a_fresh_one() {
+        fn configure(engine: &mut Engine<Counter, Tick>) {
+            engine.set_event_log(true);
+            engine.set_warmup(2.0);
+            engine.track("ticks", |c: &Counter| f64::from(c.ticks));
+        }
+        fn run(engine: &mut Engine<Counter, Tick>, left: u32) {
+            engine.schedule(Tick { at: 0.0, left });
+            engine.run_until(10.0);
+        }
+
+        let mut reused = Engine::new(Counter::default());
+        configure(&mut reused);
+        run(&mut reused, 30);
+        reused.reset(Counter::default());
+        assert_eq!(reused.now(), 0.0);
+        assert_eq!(reused.history().len(), 1);
+        assert!(reused.events().is_empty() && reused.is_empty());
+        run(&mut reused, 8);
+
+        let mut fresh = Engine::new(Counter::default());
+        configure(&mut fresh);
+        run(&mut fresh, 8);
+
+        assert_eq!(reused.state().ticks, fresh.state().ticks);
+        assert_eq!(reused.events(), fresh.events());
+        assert_eq!(reused.history().len(), fresh.history().len());
+        assert_eq!(reused.events_processed(), fresh.events_processed());
+        assert_eq!(reused.event_counts(), fresh.event_counts());
+        assert_eq!(reused.mean_queue_length(), fresh.mean_queue_length());
+        assert_eq!(reused.time_average("ticks"), fresh.time_average("ticks"));
+    }
+
+    #[test]
+    fn run_until_reports_a_drained_queue() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 1.0, left: 2 });
+        let outcome = engine.run_until(100.0);
+        assert_eq!(outcome.reason, StopReason::QueueEmpty);
+        assert_eq!(outcome.events_executed, 3);
+        assert_eq!(outcome.stopped_at, 100.0);
+    }
+
+    #[test]
+    fn run_until_reports_events_left_beyond_the_horizon() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 1.0, left: 10 });
+        let outcome = engine.run_until(2.0);
+        assert_eq!(outcome.reason, StopReason::HorizonReached);
+        assert_eq!(outcome.events_executed, 3);
+        assert_eq!(outcome.stopped_at, 2.0);
+        assert!(!engine.is_empty());
+    }
+
+    #[test]
+    fn sliced_run_matches_a_single_run() {
+        let mut whole = Engine::<Counter, Tick>::new(Counter::default());
+        whole.schedule(Tick { at: 0.0, left: 12 });
+        whole.run_until(10.0);
+
+        let mut sliced = Engine::<Counter, Tick>::new(Counter::default());
+        sliced.schedule(Tick { at: 0.0, left: 12 });
+        let mut clock = Vec::new();
+        loop {
+            let outcome = sliced.run_slice(5, 10.0);
+            clock.push(outcome.stopped_at);
+            if outcome.reason != StopReason::EventBudget {
+                assert_eq!(outcome.reason, StopReason::QueueEmpty);
+                break;
+            }
+        }
+        // 13 ticks, 0.5 apart: the clock only jumps to the horizon at the end.
+        assert_eq!(clock, vec![2.0, 4.5, 10.0]);
+        assert_eq!(sliced.state().ticks, whole.state().ticks);
+        assert_eq!(sliced.now(), whole.now());
+        assert_eq!(sliced.history().len(), whole.history().len());
+    }
+
+    #[test]
+    fn time_markers_fire_in_order_when_crossed() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        for t in [2.0, 1.0, 4.0] {
+            let sink = Rc::clone(&seen);
+            engine.add_time_marker(t, move |count: &u32| sink.borrow_mut().push((t, *count)));
+        }
+        engine.schedule(Alarm { minute: 3 });
+        engine.run_until(3.5);
+        // Both markers fire on the jump from 0 to 3, before the alarm changes the data.
+        assert_eq!(*seen.borrow(), vec![(1.0, 0), (2.0, 0)]);
+        engine.run_until(5.0);
+        assert_eq!(seen.borrow().last(), Some(&(4.0, 1)));
+        assert_eq!(seen.borrow().len(), 3);
+    }
+
+    #[test]
+    fn events_see_the_run_horizon() {
+        // A crop takes 3 time units; no new one is planted if it could not ripen in time.
+        #[derive(Clone)]
+        struct Crop(Timestamp);
+
+        impl Event<Vec<(Timestamp, Option<Timestamp>)>> for Crop {
+            fn time(&self) -> Timestamp {
+                self.0
+            }
+            fn execute(self, state: &mut State<Vec<(Timestamp, Option<Timestamp>)>, Crop>) {
+                let (now, horizon) = (state.now(), state.horizon());
+                state.state_mut().push((now, horizon));
+                if horizon.is_none_or(|h| now + 3.0 <= h) {
+                    state.schedule(Crop(now + 3.0));
+                }
+            }
+        }
+
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule(Crop(0.0));
+        engine.run_until(10.0);
+        let times: Vec<_> = engine.state().iter().map(|&(t, _)| t).collect();
+        assert_eq!(times, vec![0.0, 3.0, 6.0, 9.0]);
+        assert!(engine.state().iter().all(|&(_, h)| h == Some(10.0)));
+        assert!(engine.is_empty());
+
+        engine.schedule(Crop(12.0));
+        engine.step();
+        assert_eq!(engine.state().last(), Some(&(12.0, None)));
+    }
+
+    #[test]
+    fn inclusive_horizon_runs_the_event_on_the_edge() {
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule_periodic(0.0, 5.0, Beat);
+        engine.run_until(10.0);
+        assert_eq!(engine.state(), &vec![0.0, 5.0, 10.0]);
+        assert_eq!(engine.now(), 10.0);
+        assert_eq!(engine.peek_next_time(), Some(15.0));
+    }
+
+    #[test]
+    fn exclusive_horizon_leaves_the_event_on_the_edge_pending() {
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule_periodic(0.0, 5.0, Beat);
+        engine.run_until_exclusive(10.0);
+        assert_eq!(engine.state(), &vec![0.0, 5.0]);
+        assert_eq!(engine.now(), 10.0);
+        assert_eq!(engine.peek_next_time(), Some(10.0));
+        // The edge event is not lost: the next run starts with it, exactly once.
+        engine.run_until_exclusive(20.0);
+        assert_eq!(engine.state(), &vec![0.0, 5.0, 10.0, 15.0]);
+        engine.run_until(20.0);
+        assert_eq!(engine.state(), &vec![0.0, 5.0, 10.0, 15.0, 20.0]);
+    }
+
+    #[test]
+    fn rescheduled_event_fires_at_its_new_time() {
+        let mut engine = Engine::new(Vec::new());
+        let ScheduleOutcome::Scheduled(handle) = engine.schedule_result(Beat(5.0)) else {
+            panic!("beat was not scheduled");
+        };
+        engine.schedule(Beat(3.0));
+        assert!(engine.reschedule(handle, 2.0));
+        engine.set_past_event_policy(PastEventPolicy::Error);
+        assert!(!engine.reschedule(handle, -1.0));
+        assert_eq!(engine.pending_len(), 2);
+        engine.run_until(10.0);
+        assert_eq!(engine.state(), &vec![2.0, 3.0]);
+        // It already ran, so there is nothing left to move.
+        assert!(!engine.reschedule(handle, 8.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "period must be positive")]
+    fn periodic_events_reject_non_positive_periods() {
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        engine.schedule_periodic(0.0, 0.0, |_| Alarm { minute: 0 });
+    }
+
+    #[test]
+    fn schedule_after_adds_a_duration_to_now() {
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        engine.schedule(Alarm { minute: 3 });
+        engine.run_until(3.0);
+        engine.schedule_after(Duration(2.0), Alarm { minute: 0 });
+        assert_eq!(engine.peek_next_time(), Some(5.0));
+
+        let leg = Duration::from(1.5) * 2.0 + Duration(1.0);
+        assert_eq!(leg, Duration(4.0));
+        assert_eq!(engine.now() + leg, 7.0);
+        assert_eq!(f64::from(leg / 4.0), 1.0);
+    }
+
+    #[test]
+    fn drain_remaining_returns_pending_events_in_time_order() {
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        for minute in [9, 2, 12, 4] {
+            engine.schedule(Alarm { minute });
+        }
+        let ScheduleOutcome::Scheduled(dropped) = engine.schedule_result(Alarm { minute: 11 })
+        else {
+            panic!("alarm was not scheduled");
+        };
+        engine.cancel(dropped);
+        engine.run_until(5.0);
+
+        let remaining = engine.drain_remaining();
+        let times: Vec<_> = remaining.iter().map(|(at, _)| *at).collect();
+        assert_eq!(times, vec![9.0, 12.0]);
+        assert_eq!(remaining[0].1.minute, 9);
+        assert!(engine.is_empty());
+        engine.run_until(20.0);
+        assert_eq!(*engine.state(), 2);
+    }
+
+    #[test]
+    fn zero_delay_loops_are_stopped() {
+        #[derive(Clone)]
+        struct Spin {
+            at: Timestamp,
+        }
+
+        impl Event<u32> for Spin {
+            fn time(&self) -> Timestamp {
+                self.at
+            }
+            fn execute(self, state: &mut State<u32, Spin>) {
+                *state.state_mut() += 1;
+                // Bug: forgets to add a delay, so the clock never moves on.
+                state.schedule(Spin { at: self.at });
+            }
+        }
+
+        let mut engine = Engine::new(0);
+        engine.set_max_zero_delay_chain(1000);
+        engine.schedule(Spin { at: 2.0 });
+        assert_eq!(engine.dry_run_count(10.0), 1001);
+        let outcome = engine.run_until(10.0);
+        assert_eq!(outcome.reason, StopReason::ZeroDelayLoop);
+        assert_eq!(outcome.stopped_at, 2.0);
+        assert_eq!(outcome.events_executed, 1001);
+        assert_eq!(*engine.state(), 1001);
+        assert_eq!(engine.pending_len(), 1);
+
+        // Runs without a horizon stop too.
+        let mut engine = Engine::new(0);
+        engine.set_max_zero_delay_chain(1000);
+        engine.schedule(Spin { at: 2.0 });
+        engine.run();
+        assert_eq!(*engine.state(), 1001);
+        assert_eq!(engine.run_n_events(5), 1);
+        assert_eq!(*engine.state(), 1002);
+    }
+
+    #[test]
+    fn pending_times_can_be_shifted_and_filtered() {
+        let mut engine = Engine::new(Vec::new());
+        for at in [1.0, 2.0, 3.0, 4.0] {
+            engine.schedule(Beat(at));
+        }
+        let ScheduleOutcome::Scheduled(dropped) = engine.schedule_result(Beat(2.5)) else {
+            panic!("beat was not scheduled");
+        };
+        engine.cancel(dropped);
+        engine.run_until(1.5);
+
+        // A disruption at 1.5 delays everything still pending by 5.
+        engine.map_pending_times(|t| t + 5.0);
+        engine.retain_pending(|_, at| at != 8.0);
+        assert_eq!(engine.pending_len(), 2);
+        engine.run_until(20.0);
+        assert_eq!(engine.state(), &vec![1.0, 7.0, 9.0]);
+    }
+
+    #[test]
+    fn history_at_returns_the_latest_snapshot_not_after_t() {
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule(Beat(2.0));
+        engine.schedule(Beat(2.0));
+        engine.schedule(Beat(5.0));
+        engine.run_until(5.0);
+
+        assert_eq!(engine.history_at(-1.0), None);
+        assert_eq!(engine.history_at(1.0), Some(&vec![]));
+        // Between the snapshots at 2 and 5: the later one at 2, after both beats.
+        assert_eq!(engine.history_at(3.5), Some(&vec![2.0, 2.0]));
+        assert_eq!(engine.history_at(5.0), Some(&vec![2.0, 2.0, 5.0]));
+        assert_eq!(
+            engine.history_iter().map(|(at, _)| at).collect::<Vec<_>>(),
+            vec![0.0, 2.0, 2.0, 5.0]
+        );
+    }
+
+    #[test]
+    fn progress_is_reported_once_per_crossed_slice() {
+        let mut engine = Engine::new(Vec::new());
+        // Dense events early, then one long jump over several slices.
+        for at in [0.1, 0.2, 0.3, 1.5, 7.2] {
+            engine.schedule(Beat(at));
+        }
+        let mut fractions = Vec::new();
+        let outcome = engine.run_until_with_progress(10.0, 2.0, |f| fractions.push(f));
+        assert_eq!(outcome.events_executed, 5);
+        assert_eq!(fractions, vec![0.2, 0.4, 0.6, 0.8, 1.0]);
+
+        // The end of the run is reported even when it falls inside a slice.
+        let mut fractions = Vec::new();
+        engine.run_until_with_progress(13.0, 2.0, |f| fractions.push(f));
+        assert_eq!(fractions, vec![2.0 / 3.0, 1.0]);
+
+        // A zero-length run is complete as soon as it starts.
+        let mut fractions = Vec::new();
+        engine.run_until_with_progress(13.0, 2.0, |f| fractions.push(f));
+        assert_eq!(fractions, vec![1.0]);
+    }
+
+    #[test]
+    fn rewind_to_a_snapshot_and_rerun_differently() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        assert_eq!(engine.rewind_to(0), Err(RewindError::NotRewindable));
+        engine.set_rewindable(true);
+        engine.schedule(Tick { at: 0.0, left: 4 });
+        engine.run_n_events(5);
+        assert_eq!(engine.history().len(), 6);
+        assert_eq!(engine.rewind_to(6), Err(RewindError::OutOfRange));
+
+        engine.rewind_to(2).unwrap();
+        assert_eq!(engine.now(), 0.5);
+        assert_eq!(engine.state().ticks, 2);
+        assert_eq!(engine.events_processed(), 2);
+        assert_eq!(engine.history().len(), 3);
+        assert_eq!(engine.pending_len(), 1);
+
+        // What if the counter had been bumped at that point?
+        engine.state_mut().ticks += 10;
+        engine.run_until(10.0);
+        assert_eq!(engine.state().ticks, 15);
+        assert_eq!(engine.events_processed(), 5);
+    }
+
+    #[test]
+    fn schedule_all_queues_every_event() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let seeds: Vec<_> = (0..5)
+            .map(|i| Tick {
+                at: i as f64,
+                left: 0,
+            })
+            .collect();
+        engine.schedule_all(seeds);
+        assert_eq!(engine.pending_len(), 5);
+        engine.run_until(10.0);
+        assert_eq!(engine.state().ticks, 5);
+    }
+
+    #[test]
+    fn try_schedule_refuses_past_events_under_any_policy() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 5.0, left: 0 });
+        engine.run_until(5.0);
+        assert_eq!(
+            engine.try_schedule(Tick { at: 4.0, left: 0 }),
+            Err(ScheduleError::Past)
+        );
+        assert_eq!(
+            engine.try_schedule(Tick {
+                at: f64::INFINITY,
+                left: 0
+            }),
+            Err(ScheduleError::NonFinite)
+        );
+        assert_eq!(engine.try_schedule(Tick { at: 5.0, left: 0 }), Ok(()));
+        assert_eq!(engine.pending_len(), 1);
+        assert_eq!(engine.past_schedules(), 0);
+    }
+
+    #[test]
+    fn nan_times_are_rejected() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let nan = Tick {
+            at: f64::NAN,
+            left: 0,
+        };
+        assert_eq!(
+            engine.try_schedule(nan.clone()),
+            Err(ScheduleError::NonFinite)
+        );
+        assert_eq!(
+            engine.schedule_result(nan),
+            ScheduleOutcome::RejectedNonFinite
+        );
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "simulation time would run backwards")]
+    fn executing_a_past_event_panics_in_debug_builds() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.run_until(5.0);
+        // `schedule_result` reports the outcome instead of checking the time itself.
+        let outcome = engine.schedule_result(Tick { at: 4.0, left: 0 });
+        assert!(matches!(outcome, ScheduleOutcome::Scheduled(_)));
+        engine.run_until(10.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "non-finite time")]
+    fn schedule_at_nan_panics_in_debug_builds() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick {
+            at: f64::NAN,
+            left: 0,
+        });
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "before the current time")]
+    fn schedule_into_the_past_panics_in_debug_builds() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.run_until(5.0);
+        engine.schedule(Tick { at: 4.0, left: 0 });
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "non-finite time")]
+    fn guarded_schedule_at_nan_panics_in_debug_builds() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule_if(f64::NAN, Tick { at: 0.0, left: 0 }, |_| true);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "before the current time")]
+    fn reschedule_into_the_past_panics_in_debug_builds() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let ScheduleOutcome::Scheduled(handle) = engine.schedule_result(Tick { at: 6.0, left: 0 })
+        else {
+            panic!("tick was not scheduled");
+        };
+        engine.run_until(5.0);
+        engine.reschedule(handle, 4.0);
+    }
+
+    #[test]
+    fn schedule_result_reports_each_outcome() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let first = engine.schedule_result(Tick { at: 1.0, left: 0 });
+        let second = engine.schedule_result(Tick { at: 1.0, left: 0 });
+        match (first, second) {
+            (ScheduleOutcome::Scheduled(a), ScheduleOutcome::Scheduled(b)) => assert_ne!(a, b),
+            other => panic!("expected two scheduled events, got {other:?}"),
+        }
+        assert_eq!(
+            engine.schedule_result(Tick {
+                at: f64::NAN,
+                left: 0
+            }),
+            ScheduleOutcome::RejectedNonFinite
+        );
+
+        engine.run_until(5.0);
+        let late = Tick { at: 2.0, left: 0 };
+        assert!(matches!(
+            engine.schedule_result(late.clone()),
+            ScheduleOutcome::Scheduled(_)
+        ));
+        engine.set_past_event_policy(PastEventPolicy::Error);
+        assert_eq!(engine.schedule_result(late), ScheduleOutcome::RejectedPast);
+    }
+
+    #[test]
+    fn builder_estimate_reserves_capacity() {
+        let mut engine = Engine::<Counter, Tick>::builder(Counter::default())
+            .estimate(50.0, 2.0)
+            .build();
+        assert_eq!(engine.events.capacity(), 0);
+        engine.set_event_log(true);
+        assert!(engine.history.capacity() >= 100);
+        assert!(engine.events.capacity() >= 100);
+        let Queue::Heap(queue) = &engine.state.queue else {
+            panic!("the builder defaults to a binary heap");
+        };
+        assert!(queue.capacity() >= 100);
+
+        engine.schedule(Tick { at: 0.0, left: 99 });
+        engine.run_until(50.0);
+        assert_eq!(engine.state().ticks, 100);
+        assert_eq!(engine.events().len(), 100);
+    }
+
+    #[test]
+    fn rewind_then_rerun_reaches_same_state() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_rewindable(true);
+        engine.set_event_log(true);
+        engine.schedule(Tick { at: 0.0, left: 4 });
+        engine.run_until(10.0);
+        assert_eq!(engine.events_processed(), 5);
+        let (final_ticks, final_events) = (engine.state().ticks, engine.events().to_vec());
+
+        assert!(engine.rewind(2));
+        assert_eq!(engine.state().ticks, 3);
+        assert_eq!(engine.now(), 1.0);
+        assert_eq!(engine.events().len(), 3);
+        assert!(!engine.rewind(4));
+
+        engine.run_until(10.0);
+        assert_eq!(engine.state().ticks, final_ticks);
+        assert_eq!(engine.events(), final_events.as_slice());
+        assert_eq!(engine.now(), 10.0);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Delivery {
+        at: Timestamp,
+        amount: f64,
+    }
+
+    impl Event<()> for Delivery {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<(), Delivery>) {
+            let now = state.now();
+            let metrics = state.metrics_mut();
+            metrics.add("delivered", self.amount);
+            metrics.add("deliveries", 1.0);
+            metrics.set("last_delivery", now);
+        }
+    }
+
+    #[test]
+    fn metrics_accumulate_across_events() {
+        let mut engine = Engine::<(), Delivery>::new(());
+        engine.schedule(Delivery {
+            at: 1.0,
+            amount: 2.0,
+        });
+        engine.schedule(Delivery {
+            at: 3.0,
+            amount: 5.5,
+        });
+        engine.run_until(2.0);
+        assert_eq!(engine.metrics().get("delivered"), Some(2.0));
+        engine.run_until(4.0);
+        assert_eq!(
+            engine.history_metrics(1).and_then(|m| m.get("delivered")),
+            Some(2.0)
+        );
+        assert!(engine.history_metrics(engine.history().len()).is_none());
+
+        let metrics = engine.metrics();
+        assert_eq!(metrics.get("delivered"), Some(7.5));
+        assert_eq!(metrics.get("deliveries"), Some(2.0));
+        assert_eq!(metrics.get("last_delivery"), Some(3.0));
+        assert_eq!(metrics.get("spoiled"), None);
+    }
+
+    #[derive(Clone, Debug)]
+    enum Farm {
+        Plant { at: Timestamp },
+        Harvest { at: Timestamp },
+        Deliver { at: Timestamp },
+    }
+
+    impl Event<u32> for Farm {
+        fn time(&self) -> Timestamp {
+            match *self {
+                Farm::Plant { at } | Farm::Harvest { at } | Farm::Deliver { at } => at,
+            }
+        }
+        fn label(&self) -> &'static str {
+            match self {
+                Farm::Plant { .. } => "plant",
+                Farm::Harvest { .. } => "harvest",
+                Farm::Deliver { .. } => "deliver",
+            }
+        }
+        fn execute(self, state: &mut State<u32, Farm>) {
+            let now = state.now();
+            match self {
+                Farm::Plant { .. } => state.schedule(Farm::Harvest { at: now + 3.0 }),
+                Farm::Harvest { .. } => state.schedule(Farm::Deliver { at: now + 1.0 }),
+                Farm::Deliver { .. } => {
+                    *state.state_mut() += 1;
+                    state.schedule(Farm::Plant { at: now });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn current_event_label_is_set_during_execute() {
+        #[derive(Clone, Debug)]
+        enum Yard {
+            Delivery { at: Timestamp },
+            Inspection { at: Timestamp },
+        }
+
+        /// Shared by both events; only deliveries bring work.
+        fn maybe_spawn_worker(state: &mut State<Vec<&'static str>, Yard>) {
+            let trigger = state.current_event_label().expect("called from execute");
+            state.state_mut().push(trigger);
+            if trigger == "delivery" {
+                state.state_mut().push("worker");
+            }
+        }
+
+        impl Event<Vec<&'static str>> for Yard {
+            fn time(&self) -> Timestamp {
+                match *self {
+                    Yard::Delivery { at } | Yard::Inspection { at } => at,
+                }
+            }
+            fn label(&self) -> &'static str {
+                match self {
+                    Yard::Delivery { .. } => "delivery",
+                    Yard::Inspection { .. } => "inspection",
+                }
+            }
+            fn execute(self, state: &mut State<Vec<&'static str>, Yard>) {
+                assert_eq!(state.current_event_label(), Some(self.label()));
+                maybe_spawn_worker(state);
+            }
+        }
+
+        let mut engine = Engine::new(Vec::new());
+        engine.schedule(Yard::Delivery { at: 1.0 });
+        engine.schedule(Yard::Inspection { at: 2.0 });
+        assert_eq!(engine.state.current_event_label(), None);
+        engine.run_until(5.0);
+        assert_eq!(engine.state(), &vec!["delivery", "worker", "inspection"]);
+        assert_eq!(engine.state.current_event_label(), None);
+    }
+
+    #[test]
+    fn run_until_event_stops_after_first_match() {
+        let mut engine = Engine::<u32, Farm>::new(0);
+        engine.schedule(Farm::Plant { at: 0.0 });
+        let reason = engine.run_until_event(100.0, |ev| matches!(ev, Farm::Deliver { .. }));
+        assert_eq!(reason, StopReason::MatchedEvent);
+        assert_eq!(*engine.state(), 1);
+        assert_eq!(engine.now(), 4.0);
+        assert_eq!(engine.events_processed(), 3);
+
+        let reason = engine.run_until_event(6.0, |ev| matches!(ev, Farm::Deliver { .. }));
+        assert_eq!(reason, StopReason::HorizonReached);
+        assert_eq!(engine.now(), 6.0);
+    }
+
+    #[test]
+    fn new_at_rejects_seeds_before_start_under_error_policy() {
+        let mut engine = Engine::<Counter, Tick>::new_at(Counter::default(), 100.0);
+        assert_eq!(engine.now(), 100.0);
+        assert_eq!(engine.history()[0].0, 100.0);
+
+        engine.set_past_event_policy(PastEventPolicy::Error);
+        assert_eq!(
+            engine.schedule_result(Tick { at: 0.0, left: 0 }),
+            ScheduleOutcome::RejectedPast
+        );
+        assert!(matches!(
+            engine.schedule_result(Tick { at: 100.0, left: 1 }),
+            ScheduleOutcome::Scheduled(_)
+        ));
+        engine.run_until(110.0);
+        assert_eq!(engine.state().ticks, 2);
+    }
+
+    #[test]
+    fn run_with_source_interleaves_external_events() {
+        let mut engine = Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new(Vec::new());
+        engine.schedule(Probe::Chain {
+            at: 0.0,
+            step: 1.0,
+            left: 4,
+        });
+        let trace = [0.5, 1.0, 2.5, 7.0].map(|at| Probe::Once { at });
+        let mut trace = trace.into_iter();
+        engine.run_with_source(5.0, trace.by_ref());
+
+        let fired: Vec<Timestamp> = engine.state().iter().map(|&(now, _)| now).collect();
+        assert_eq!(fired, vec![0.0, 0.5, 1.0, 1.0, 2.0, 2.5, 3.0, 4.0]);
+        assert_eq!(engine.now(), 5.0);
+        // The trace item past the horizon waits in the queue for the next run.
+        assert_eq!(engine.pending_by_time().len(), 1);
+        assert!(trace.next().is_none());
+    }
+
+    #[test]
+    fn run_with_source_validates_external_events() {
+        let mut engine = Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new(Vec::new());
+        engine.set_max_time(Some(8.0));
+        let trace = [1.0, 3.0, 2.0, 0.5, 4.0, 6.0, 2.5, 9.0, 7.0];
+        let mut trace = trace.map(|at| Probe::Once { at }).into_iter();
+        engine.run_with_source(5.0, trace.by_ref());
+        // 2.0 arrives late but before the clock gets there; 0.5 is already past.
+        let fired: Vec<Timestamp> = engine.state().iter().map(|&(now, _)| now).collect();
+        assert_eq!(fired, vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(engine.past_schedules(), 1);
+
+        // Resuming: 2.5 was left in the source and is now past, 9.0 is beyond the cap.
+        engine.run_with_source(10.0, trace);
+        let fired: Vec<Timestamp> = engine.state().iter().map(|&(now, _)| now).collect();
+        assert_eq!(fired, vec![1.0, 2.0, 3.0, 4.0, 6.0, 7.0]);
+        assert_eq!(engine.past_schedules(), 2);
+    }
+
+    #[test]
+    fn run_with_source_stops_on_zero_delay_loops() {
+        let mut engine = Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new(Vec::new());
+        engine.set_max_zero_delay_chain(3);
+        let trace = [1.0, 1.0, 3.0].map(|at| Probe::Chain {
+            at,
+            step: 0.0,
+            left: 10,
+        });
+        let mut trace = trace.into_iter();
+        let outcome = engine.run_with_source(5.0, trace.by_ref());
+        assert_eq!(outcome.reason, StopReason::ZeroDelayLoop);
+        assert_eq!(outcome.stopped_at, 1.0);
+        assert_eq!(outcome.events_executed, 4);
+        // The source item already read waits in the queue with both looping chains.
+        assert_eq!(trace.len(), 0);
+        assert_eq!(engine.pending_by_time().len(), 3);
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct Crew {
+        idle: u32,
+        cleaned: Vec<Timestamp>,
+    }
+
+    #[derive(Clone, Debug)]
+    struct Job {
+        at: Timestamp,
+    }
+
+    impl Event<Crew> for Job {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<Crew, Job>) {
+            state.state_mut().idle += 1;
+        }
+        fn on_cancel(self, state: &mut State<Crew, Job>) {
+            let crew = state.state_mut();
+            crew.idle += 1;
+            crew.cleaned.push(self.at);
+        }
+    }
+
+    #[test]
+    fn cancel_all_runs_cleanup_and_empties_queue() {
+        let mut engine = Engine::<Crew, Job>::new(Crew::default());
+        for at in [3.0, 1.0, 2.0] {
+            // Each job reserves a worker until it completes.
+            engine.schedule(Job { at });
+        }
+        engine.cancel_all(true);
+        assert_eq!(engine.state().idle, 3);
+        assert_eq!(engine.state().cleaned, vec![1.0, 2.0, 3.0]);
+        assert!(engine.pending_by_time().is_empty());
+
+        engine.schedule(Job { at: 4.0 });
+        engine.cancel_all(false);
+        assert_eq!(engine.state().idle, 3);
+        engine.run_until(10.0);
+        assert_eq!(engine.events_processed(), 0);
+    }
+
+    #[test]
+    fn on_cancel_runs_once_for_a_cancelled_event_only() {
+        let mut engine = Engine::<Crew, Job>::new(Crew::default());
+        let ScheduleOutcome::Scheduled(timeout) = engine.schedule_result(Job { at: 2.0 }) else {
+            panic!("timeout was not scheduled");
+        };
+        engine.schedule(Job { at: 1.0 });
+        engine.cancel(timeout);
+        engine.cancel(timeout);
+        engine.run_until(10.0);
+
+        assert_eq!(engine.state().cleaned, vec![2.0]);
+        assert_eq!(engine.state().idle, 2);
+        assert_eq!(engine.events_processed(), 1);
+    }
+
+    #[test]
+    fn shutdown_event_clears_the_queue() {
+        #[derive(Clone, Debug)]
+        enum Plant {
+            Work { at: Timestamp },
+            Shutdown { at: Timestamp, cleanup: bool },
+        }
+
+        impl Event<Crew> for Plant {
+            fn time(&self) -> Timestamp {
+                match *self {
+                    Plant::Work { at } | Plant::Shutdown { at, .. } => at,
+                }
+            }
+            fn execute(self, state: &mut State<Crew, Plant>) {
+                match self {
+                    Plant::Work { at } => {
+                        state.state_mut().idle += 1;
+                        state.schedule(Plant::Work { at: at + 1.0 });
+                    }
+                    // Everyone goes home: nothing pending may run.
+                    Plant::Shutdown { cleanup: true, .. } => state.cancel_all(true),
+                    Plant::Shutdown { cleanup: false, .. } => state.clear_queue(),
+                }
+            }
+            fn on_cancel(self, state: &mut State<Crew, Plant>) {
+                let now = state.now();
+                state.state_mut().cleaned.push(now);
+            }
+        }
+
+        let run = |cleanup| {
+            let mut engine = Engine::<Crew, Plant>::new(Crew::default());
+            engine.schedule(Plant::Work { at: 0.5 });
+            engine.schedule(Plant::Shutdown { at: 3.0, cleanup });
+            engine.schedule(Plant::Work { at: 8.0 });
+            let outcome = engine.run_until(f64::INFINITY);
+            assert_eq!(outcome.reason, StopReason::QueueEmpty);
+            assert_eq!(engine.now(), 3.0);
+            assert_eq!(engine.state().idle, 3);
+            engine.state().cleaned.clone()
+        };
+        // The pending shift at 3.5 and the one at 8 were both cleaned up.
+        assert_eq!(run(true), vec![3.0, 3.0]);
+        // `clear_queue` drops them without cleanup.
+        assert!(run(false).is_empty());
+    }
+
+    #[test]
+    fn profile_report_counts_every_event() {
+        let mut engine = Engine::<u32, Farm>::new(0);
+        engine.set_profiling(true);
+        engine.schedule(Farm::Plant { at: 0.0 });
+        engine.run_until(20.0);
+
+        let report = engine.profile_report();
+        let total: u64 = report.iter().map(|(_, _, _, count)| count).sum();
+        assert_eq!(total as usize, engine.events_processed());
+        let mut labels: Vec<&str> = report.iter().map(|(label, ..)| label.as_str()).collect();
+        labels.sort();
+        assert_eq!(labels, vec!["deliver", "harvest", "plant"]);
+        assert!(report.iter().all(|(_, total, max, _)| max <= total));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn state_json_exposes_fields_and_time() {
+        #[derive(Clone, Debug, serde::Serialize)]
+        struct Stock {
+            wheat: u32,
+            label: &'static str,
+        }
+
+        #[derive(Clone, Debug)]
+        struct Harvest {
+            at: Timestamp,
+        }
+
+        impl Event<Stock> for Harvest {
+            fn time(&self) -> Timestamp {
+                self.at
+            }
+            fn execute(self, state: &mut State<Stock, Harvest>) {
+                state.state_mut().wheat += 4;
+            }
+        }
+
+        let mut engine = Engine::<Stock, Harvest>::new(Stock {
+            wheat: 1,
+            label: "north",
+        });
+        engine.schedule(Harvest { at: 2.0 });
+        engine.run_until(2.5);
+
+        let value = engine.state_json().unwrap();
+        assert_eq!(value["now"], 2.5);
+        assert_eq!(value["state"]["wheat"], 5);
+        assert_eq!(value["state"]["label"], "north");
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn event_log_exports_to_json() {
+        #[derive(Clone, Debug)]
+        struct Harvest {
+            at: Timestamp,
+            field: &'static str,
+        }
+
+        impl Event<Vec<&'static str>> for Harvest {
+            fn time(&self) -> Timestamp {
+                self.at
+            }
+            fn execute(self, state: &mut State<Vec<&'static str>, Harvest>) {
+                state.state_mut().push(self.field);
+            }
+        }
+
+        let mut engine = Engine::<_, Harvest>::new(Vec::new());
+        engine.set_event_log(true);
+        engine.schedule(Harvest {
+            at: 1.0,
+            field: "north \"upper\"",
+        });
+        engine.schedule(Harvest {
+            at: 2.0,
+            field: "south",
+        });
+        engine.run_until(3.0);
+
+        #[derive(serde::Deserialize)]
+        struct Entry {
+            time: f64,
+            label: String,
+        }
+        let entries: Vec<Entry> = serde_json::from_str(&engine.events_to_json()).unwrap();
+        assert_eq!(entries.len(), engine.events().len());
+        assert_eq!(entries[0].time, engine.events()[0].0);
+        assert_eq!(entries[0].label, engine.events()[0].1);
+        assert!(entries[0].label.contains(r#"\"upper\""#));
+    }
+
+    #[derive(Clone, Debug)]
+    enum Step {
+        Mark { at: Timestamp, name: &'static str },
+        SpawnLast { at: Timestamp, name: &'static str },
+    }
+
+    impl Event<Vec<&'static str>> for Step {
+        fn time(&self) -> Timestamp {
+            match *self {
+                Step::Mark { at, .. } | Step::SpawnLast { at, .. } => at,
+            }
+        }
+        fn execute(self, state: &mut State<Vec<&'static str>, Step>) {
+            match self {
+                Step::Mark { name, .. } => state.state_mut().push(name),
+                Step::SpawnLast { at, name } => {
+                    state.state_mut().push(name);
+                    state.schedule_now_last(Step::Mark { at, name: "last" });
+                    state.schedule(Step::Mark { at, name: "late" });
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn schedule_now_last_fires_after_same_time_events() {
+        let mut engine = Engine::<Vec<&'static str>, Step>::new(Vec::new());
+        engine.schedule(Step::SpawnLast {
+            at: 1.0,
+            name: "spawn",
+        });
+        engine.schedule(Step::Mark { at: 1.0, name: "a" });
+        engine.schedule(Step::Mark { at: 1.0, name: "b" });
+        engine.schedule(Step::Mark {
+            at: 2.0,
+            name: "next",
+        });
+        engine.run_until(5.0);
+        assert_eq!(
+            engine.state(),
+            &vec!["spawn", "a", "b", "late", "last", "next"]
+        );
+    }
+
+    #[test]
+    fn run_by_time_step_reports_once_per_instant() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        for at in [1.0, 1.0, 2.0] {
+            engine.schedule(Tick { at, left: 0 });
+        }
+        let mut frames = Vec::new();
+        engine.run_by_time_step(5.0, |t, counter| frames.push((t, counter.ticks)));
+        assert_eq!(frames, vec![(1.0, 2), (2.0, 3)]);
+        assert_eq!(engine.now(), 5.0);
+    }
+
+    #[test]
+    fn run_by_time_step_stops_on_zero_delay_loops() {
+        let mut engine = Engine::<Vec<(Timestamp, Timestamp)>, Probe>::new(Vec::new());
+        engine.set_max_zero_delay_chain(3);
+        engine.schedule(Probe::Once { at: 1.0 });
+        engine.schedule(Probe::Chain {
+            at: 2.0,
+            step: 0.0,
+            left: 10,
+        });
+        let mut frames = Vec::new();
+        let outcome = engine.run_by_time_step(5.0, |t, _| frames.push(t));
+        assert_eq!(outcome.reason, StopReason::ZeroDelayLoop);
+        assert_eq!(outcome.stopped_at, 2.0);
+        assert_eq!(frames, vec![1.0]);
+        assert_eq!(engine.history().last().unwrap().0, 2.0);
+    }
+
+    #[test]
+    fn max_time_rejects_events_beyond_cap() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_max_time(Some(100.0));
+        assert!(matches!(
+            engine.schedule_result(Tick { at: 100.0, left: 0 }),
+            ScheduleOutcome::Scheduled(_)
+        ));
+        assert_eq!(
+            engine.schedule_result(Tick { at: 1e300, left: 0 }),
+            ScheduleOutcome::RejectedBeyondMaxTime
+        );
+        engine.set_max_time(None);
+        assert!(matches!(
+            engine.schedule_result(Tick { at: 1e300, left: 0 }),
+            ScheduleOutcome::Scheduled(_)
+        ));
+    }
+
+    #[derive(Clone, Debug)]
+    struct Ranked {
+        at: Timestamp,
+        name: &'static str,
+        priority: i32,
+        last: bool,
+    }
+
+    impl Event<Vec<&'static str>> for Ranked {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+        fn execute(self, state: &mut State<Vec<&'static str>, Ranked>) {
+            state.state_mut().push(self.name);
+        }
+    }
+
+    /// Locks the documented firing order; a change here is a breaking change.
+    #[test]
+    fn same_time_firing_order_is_stable() {
+        let mut engine = Engine::<Vec<&'static str>, Ranked>::new_at(Vec::new(), 1.0);
+        let plan = [
+            ("p1-a", 1, false),
+            ("last-a", -5, true),
+            ("p0-a", 0, false),
+            ("p1-b", 1, false),
+            ("neg", -2, false),
+            ("last-b", 0, true),
+            ("p0-b", 0, false),
+        ];
+        for (name, priority, last) in plan {
+            let event = Ranked {
+                at: 1.0,
+                name,
+                priority,
+                last,
+            };
+            if event.last {
+                engine.state.schedule_now_last(event);
+            } else {
+                engine.schedule(event);
+            }
+        }
+        engine.schedule(Ranked {
+            at: 0.5 + 0.5,
+            name: "p1-c",
+            priority: 1,
+            last: false,
+        });
+        engine.run_until(2.0);
+        assert_eq!(
+            engine.state(),
+            &vec!["neg", "p0-a", "p0-b", "p1-a", "p1-b", "p1-c", "last-a", "last-b"]
+        );
+    }
+
+    #[test]
+    fn scheduled_entries_at_the_same_time_are_distinct() {
+        let entry =
+            |seq| Scheduled::<Counter, Tick, _>::new(1.0, 0, seq, None, Tick { at: 1.0, left: 0 });
+        let (first, second) = (entry(0), entry(1));
+        assert!(first != second);
+        assert!(first == first.clone());
+        assert!(first > second, "the earlier-scheduled entry pops first");
+    }
+
+    #[test]
+    fn departures_fire_before_arrivals_at_the_same_time() {
+        let mut engine = Engine::<Vec<&'static str>, Ranked>::new(Vec::new());
+        // Scheduled first, but the lower priority value wins the tie.
+        engine.schedule(Ranked {
+            at: 3.0,
+            name: "arrival",
+            priority: 1,
+            last: false,
+        });
+        engine.schedule(Ranked {
+            at: 3.0,
+            name: "departure",
+            priority: 0,
+            last: false,
+        });
+        engine.run_until(5.0);
+        assert_eq!(engine.state(), &vec!["departure", "arrival"]);
+    }
+
+    #[test]
+    fn phases_run_in_order_within_an_instant() {
+        #[derive(Clone)]
+        struct Phased {
+            name: &'static str,
+            phase: u8,
+            priority: i32,
+        }
+
+        impl Event<Vec<&'static str>> for Phased {
+            fn time(&self) -> Timestamp {
+                1.0
+            }
+            fn phase(&self) -> u8 {
+                self.phase
+            }
+            fn priority(&self) -> i32 {
+                self.priority
+            }
+            fn execute(self, state: &mut State<Vec<&'static str>, Phased>) {
+                state.state_mut().push(self.name);
+            }
+        }
+
+        let mut engine = Engine::new(Vec::new());
+        let plan = [
+            ("observe-a", 1, -9),
+            ("update-a", 0, 0),
+            ("observe-b", 1, -9),
+            ("update-b", 0, 5),
+            ("update-c", 0, 0),
+        ];
+        for (name, phase, priority) in plan {
+            engine.schedule(Phased {
+                name,
+                phase,
+                priority,
+            });
+        }
+        engine.run_until(2.0);
+        assert_eq!(
+            engine.state(),
+            &vec!["update-a", "update-c", "update-b", "observe-a", "observe-b"]
+        );
+    }
+
+    #[test]
+    fn on_schedule_sees_every_accepted_event() {
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let sink = Rc::clone(&seen);
+        engine.on_schedule(move |now, tick: &Tick| sink.borrow_mut().push((now, tick.at)));
+
+        engine.schedule(Tick { at: 1.0, left: 2 });
+        let _ = engine.schedule_result(Tick {
+            at: f64::NAN,
+            left: 0,
+        });
+        let predicted = engine.dry_run_count(10.0);
+        engine.run_until(10.0);
+
+        assert_eq!(predicted, 3);
+        assert_eq!(*seen.borrow(), vec![(0.0, 1.0), (1.0, 1.5), (1.5, 2.0)]);
+    }
+
+    #[test]
+    fn execute_hooks_bracket_every_event() {
+        let trace = Rc::new(RefCell::new(Vec::new()));
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        let sink = Rc::clone(&trace);
+        engine.on_before(move |at, counter: &Counter| {
+            sink.borrow_mut().push(("before", at, counter.ticks))
+        });
+        let sink = Rc::clone(&trace);
+        engine.on_after(move |at, counter: &Counter| {
+            sink.borrow_mut().push(("after", at, counter.ticks))
+        });
+        engine.schedule(Tick { at: 1.0, left: 2 });
+        engine.run_until(10.0);
+
+        // history = initial snapshot, one per event, closing snapshot at the horizon.
+        let history = engine.history();
+        let expected: Vec<_> = history
+            .windows(2)
+            .take(3)
+            .flat_map(|pair| {
+                [
+                    ("before", pair[1].0, pair[0].1.ticks),
+                    ("after", pair[1].0, pair[1].1.ticks),
+                ]
+            })
+            .collect();
+        assert_eq!(*trace.borrow(), expected);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    #[tracing_test::traced_test]
+    fn each_execution_runs_in_an_event_span() {
+        #[derive(Clone)]
+        struct Shout(Timestamp);
+        impl Event<()> for Shout {
+            fn time(&self) -> Timestamp {
+                self.0
+            }
+            fn label(&self) -> &'static str {
+                "shout"
+            }
+            fn execute(self, _: &mut State<(), Shout>) {
+                tracing::info!("executing");
+            }
+        }
+
+        let mut engine = Engine::new(());
+        for at in [1.0, 2.0, 3.0] {
+            engine.schedule(Shout(at));
+        }
+        engine.run_until(5.0);
+        logs_assert(|lines: &[&str]| {
+            let spans = ["at=1.0", "at=2.0", "at=3.0"].map(|at| {
+                lines
+                    .iter()
+                    .filter(|line| line.contains(&format!("event{{label=shout {at}}}")))
+                    .count()
+            });
+            match spans {
+                [1, 1, 1] => Ok(()),
+                counts => Err(format!("expected one span per event, found {counts:?}")),
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "NaN")]
+    fn nan_horizon_panics() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.run_until(f64::NAN);
+    }
+
+    #[test]
+    fn horizon_behind_clock_is_a_no_op() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 0 });
+        engine.run_until(-1.0);
+        assert_eq!(engine.state().ticks, 0);
+        assert_eq!(engine.now(), 0.0);
+        assert_eq!(engine.history().len(), 1);
+
+        engine.run_until(3.0);
+        engine.run_until(f64::NEG_INFINITY);
+        assert_eq!(engine.now(), 3.0);
+    }
+
+    #[test]
+    fn infinite_horizon_drains_the_queue() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.run_until(f64::INFINITY);
+        assert_eq!(engine.state().ticks, 4);
+        assert!(engine.pending_by_time().is_empty());
+        // The clock stays at the last tick instead of jumping to infinity.
+        assert_eq!(engine.now(), 1.5);
+        assert_eq!(engine.history().last().map(|(at, _)| *at), Some(1.5));
+        assert!(engine.mean_queue_length().is_finite());
+    }
+
+    #[test]
+    fn tick_chain_has_linear_causal_tree() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_track_causality(true);
+        let ScheduleOutcome::Scheduled(root) = engine.schedule_result(Tick { at: 0.0, left: 3 })
+        else {
+            panic!("root tick rejected");
+        };
+        engine.run_until(10.0);
+
+        let tree = engine.causal_tree();
+        assert_eq!(tree.len(), 4);
+        assert_eq!(tree[0], (root, None));
+        for pair in tree.windows(2) {
+            assert_eq!(pair[1].1, Some(pair[0].0));
+        }
+
+        let edges = engine.causality();
+        assert_eq!(edges[0], (ROOT_PARENT, root.seq()));
+        for pair in edges.windows(2) {
+            assert_eq!(pair[1].0, pair[0].1);
+        }
+
+        // Untracked runs record nothing.
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.run_until(10.0);
+        assert!(engine.causal_tree().is_empty());
+    }
+
+    #[test]
+    fn causal_tree_records_every_child_of_a_branching_event() {
+        struct Split(Timestamp, u32);
+
+        impl Event<()> for Split {
+            fn time(&self) -> Timestamp {
+                self.0
+            }
+            fn execute(self, state: &mut State<(), Split>) {
+                for _ in 0..self.1 {
+                    state.schedule(Split(self.0 + 1.0, self.1 - 1));
+                }
+            }
+        }
+
+        let mut engine = Engine::new(());
+        engine.schedule(Split(0.0, 2));
+        engine.schedule(Split(0.5, 0));
+        engine.set_track_causality(true);
+        engine.run_until(10.0);
+
+        // Two roots; the first has two children, each with one child of its own.
+        let tree = engine.causal_tree();
+        assert_eq!(tree.len(), 6);
+        let children = |parent| tree.iter().filter(|(_, p)| *p == parent).count();
+        assert_eq!(children(None), 2);
+        assert_eq!(children(Some(tree[0].0)), 2);
+        for &(child, parent) in &tree[2..] {
+            let parent = parent.expect("scheduled during a run");
+            assert!(parent < child);
+            assert!(tree.iter().any(|(handle, _)| *handle == parent));
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Cool {
+        at: Timestamp,
+    }
+
+    impl Event<f64> for Cool {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<f64, Cool>) {
+            // Relax halfway towards the ambient temperature every step.
+            let temp = state.state_mut();
+            *temp = 20.0 + (*temp - 20.0) / 2.0;
+            state.schedule(Cool { at: self.at + 1.0 });
+        }
+    }
+
+    #[test]
+    fn run_until_or_stable_stops_when_metric_settles() {
+        let mut engine = Engine::<f64, Cool>::new(100.0).with_sample_interval(4.0);
+        engine.schedule(Cool { at: 0.0 });
+        let reason = engine.run_until_or_stable(1000.0, |temp| *temp, 0.01, 3);
+        assert_eq!(reason, StopReason::Stable);
+        assert!(engine.now() < 20.0);
+        assert!((engine.state() - 20.0).abs() < 0.01);
+        // The settled state is recorded even between samples.
+        assert_eq!(
+            engine.history().last(),
+            Some(&(engine.now(), *engine.state()))
+        );
+
+        let mut short = Engine::<f64, Cool>::new(100.0);
+        short.schedule(Cool { at: 0.0 });
+        let reason = short.run_until_or_stable(3.0, |temp| *temp, 0.01, 3);
+        assert_eq!(reason, StopReason::HorizonReached);
+        assert_eq!(short.now(), 3.0);
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Alarm {
+        minute: u32,
+    }
+
+    impl Event<u32> for Alarm {
+        fn time(&self) -> Timestamp {
+            f64::from(self.minute)
+        }
+        fn execute(self, state: &mut State<u32, Alarm>) {
+            *state.state_mut() += 1;
+        }
+    }
+
+    #[test]
+    fn dedup_identical_keeps_one_pending_copy() {
+        let mut engine = Engine::<u32, Alarm>::builder(0)
+            .dedup_identical(true)
+            .build();
+        assert!(matches!(
+            engine.schedule_result(Alarm { minute: 5 }),
+            ScheduleOutcome::Scheduled(_)
+        ));
+        assert_eq!(
+            engine.schedule_result(Alarm { minute: 5 }),
+            ScheduleOutcome::Coalesced
+        );
+        engine.schedule(Alarm { minute: 6 });
+        engine.run_until(5.0);
+        assert_eq!(*engine.state(), 1);
+
+        // Once fired, the same alarm may be scheduled again.
+        assert!(matches!(
+            engine.schedule_result(Alarm { minute: 5 }),
+            ScheduleOutcome::Scheduled(_)
+        ));
+        engine.run_until(10.0);
+        assert_eq!(*engine.state(), 3);
+
+        let mut plain = Engine::<u32, Alarm>::new(0);
+        plain.schedule(Alarm { minute: 5 });
+        plain.schedule(Alarm { minute: 5 });
+        plain.run_until(10.0);
+        assert_eq!(*plain.state(), 2);
+    }
+
+    #[test]
+    fn instant_history_keeps_last_state_per_time() {
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        for _ in 0..3 {
+            engine.schedule(Alarm { minute: 1 });
+        }
+        engine.schedule(Alarm { minute: 2 });
+        engine.run_until(5.0);
+        // Initial snapshot, one per event, plus the closing snapshot at the horizon.
+        assert_eq!(engine.history().len(), 6);
+        assert_eq!(
+            engine.instant_history(),
+            vec![(0.0, &0), (1.0, &3), (2.0, &4), (5.0, &4)]
+        );
+    }
+
+    #[derive(Clone, Debug)]
+    struct Worker;
+
+    impl Event<u32> for Worker {
+        fn time(&self) -> Timestamp {
+            0.0
+        }
+        fn execute(self, state: &mut State<u32, Worker>) {
+            *state.state_mut() += 1;
+            state.reschedule_self_every(2.0, |_| Worker);
+        }
+    }
+
+    #[test]
+    fn reschedule_self_every_repeats_within_horizon() {
+        let mut engine = Engine::new(0);
+        engine.schedule(Worker);
+        engine.run_until(9.0);
+        // Fires at 0, 2, 4, 6 and 8.
+        assert_eq!(*engine.state(), 5);
+        assert_eq!(engine.pending_by_time()[0].0, 10.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "positive")]
+    fn reschedule_self_every_rejects_zero_period() {
+        let mut state = State::<u32, Worker>::new(0);
+        state.reschedule_self_every(0.0, |_| Worker);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "non-finite time")]
+    fn reschedule_self_every_flags_overflowing_times_in_debug_builds() {
+        let mut state = State::<u32, Worker>::new(0);
+        state.now = f64::MAX;
+        state.reschedule_self_every(f64::MAX, |_| Worker);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Keyed {
+        at: Timestamp,
+        priority: i32,
+        id: u64,
+    }
+
+    impl Event<Vec<u64>> for Keyed {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+        fn execute(self, state: &mut State<Vec<u64>, Keyed>) {
+            state.state_mut().push(self.id);
+        }
+    }
+
+    #[test]
+    fn schedule_key_matches_pop_order() {
+        for kind in [QueueKind::BinaryHeap, QueueKind::Calendar] {
+            // Small LCG so the test stays deterministic without the `rand` feature.
+            let mut seed: u64 = 42;
+            let mut next = move || {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                seed >> 33
+            };
+            let mut engine = Engine::builder(Vec::new()).queue(kind).build();
+            let mut keys = Vec::new();
+            for id in 0..200 {
+                let at = (next() % 10) as f64 * 0.5;
+                let priority = (next() % 3) as i32 - 1;
+                let ScheduleOutcome::Scheduled(handle) =
+                    engine.schedule_result(Keyed { at, priority, id })
+                else {
+                    panic!("event {id} was not scheduled");
+                };
+                keys.push((
+                    ScheduleKey {
+                        at,
+                        rank: i64::from(priority),
+                        seq: handle.0,
+                    },
+                    id,
+                ));
+            }
+            keys.sort_by_key(|&(key, _)| key);
+            engine.run_until(10.0);
+            let expected: Vec<u64> = keys.into_iter().map(|(_, id)| id).collect();
+            assert_eq!(*engine.state(), expected, "{kind:?}");
+        }
+    }
+
+    /// Ids of `events` in the order they execute after being scheduled in slice order.
+    fn execution_order(kind: QueueKind, events: &[(u8, i32, u64)]) -> Vec<u64> {
+        let mut engine = Engine::builder(Vec::new()).queue(kind).build();
+        engine.schedule_all(events.iter().map(|&(at, priority, id)| Keyed {
+            at: f64::from(at) * 0.5,
+            priority,
+            id,
+        }));
+        engine.run_until(f64::INFINITY);
+        engine.state().clone()
+    }
+
+    proptest! {
+        /// Scheduling the same events in any order gives the same sequence of times and
+        /// priorities, ties beyond that fire first in, first out, and the outcome never
+        /// depends on the queue's memory layout.
+        #[test]
+        fn execution_order_is_deterministic(
+            (events, shuffled) in prop::collection::vec((0u8..6, -2i32..3), 1..60)
+                .prop_map(|keys| {
+                    keys.into_iter()
+                        .zip(0..)
+                        .map(|((at, priority), id)| (at, priority, id))
+                        .collect::<Vec<_>>()
+                })
+                .prop_flat_map(|events| {
+                    let shuffled = Just(events.clone()).prop_shuffle();
+                    (Just(events), shuffled)
+                })
+        ) {
+            let key = |id: u64| {
+                let (at, priority, _) = events[id as usize];
+                (at, priority)
+            };
+            let original = execution_order(QueueKind::BinaryHeap, &events);
+            let reordered = execution_order(QueueKind::BinaryHeap, &shuffled);
+            let keys: Vec<_> = original.iter().map(|&id| key(id)).collect();
+            prop_assert!(keys.windows(2).all(|pair| pair[0] <= pair[1]));
+            prop_assert_eq!(
+                &keys,
+                &reordered.iter().map(|&id| key(id)).collect::<Vec<_>>()
+            );
+
+            // Among equal keys, events fire in the order they were scheduled.
+            let position = |id: u64| shuffled.iter().position(|e| e.2 == id);
+            for pair in reordered.windows(2) {
+                if key(pair[0]) == key(pair[1]) {
+                    prop_assert!(position(pair[0]) < position(pair[1]));
+                }
+            }
+
+            prop_assert_eq!(&reordered, &execution_order(QueueKind::BinaryHeap, &shuffled));
+            prop_assert_eq!(&reordered, &execution_order(QueueKind::Calendar, &shuffled));
+        }
+    }
+
+    #[test]
+    fn context_is_readable_in_events_but_not_snapshotted() {
+        // Not `Clone`, so no history snapshot could hold a copy of it.
+        struct Line {
+            speed: f64,
+            length: f64,
+        }
+
+        #[derive(Clone, Debug, PartialEq)]
+        struct Crate(Timestamp);
+
+        impl Event<Vec<Timestamp>, Timestamp, Line> for Crate {
+            fn time(&self) -> Timestamp {
+                self.0
+            }
+            fn execute(self, state: &mut State<Vec<Timestamp>, Crate, Timestamp, Line>) {
+                let line = state.ctx();
+                let arrives = state.now() + line.length / line.speed;
+                state.state_mut().push(arrives);
+            }
+        }
+
+        let mut engine = Engine::with_context(
+            Line {
+                speed: 2.0,
+                length: 10.0,
+            },
+            Vec::new(),
+        );
+        engine.schedule(Crate(1.0));
+        engine.schedule(Crate(3.0));
+        engine.run_until(4.0);
+        assert_eq!(engine.state(), &vec![6.0, 8.0]);
+        assert_eq!(engine.history().len(), 4);
+        assert_eq!(engine.ctx().speed, 2.0);
+    }
+
+    #[test]
+    fn meta_is_retrievable_by_type() {
+        #[derive(Debug, PartialEq)]
+        struct Params {
+            farms: u32,
+            horizon: Timestamp,
+        }
+
+        let mut engine = Engine::<u32, Alarm>::new(0);
+        engine.set_meta(Params {
+            farms: 3,
+            horizon: 10.0,
+        });
+        engine.set_meta("baseline");
+        engine.schedule(Alarm { minute: 1 });
+        engine.run_until(10.0);
+
+        assert_eq!(
+            engine.get_meta::<Params>(),
+            Some(&Params {
+                farms: 3,
+                horizon: 10.0
+            })
+        );
+        assert_eq!(engine.get_meta::<&str>(), Some(&"baseline"));
+        assert_eq!(engine.get_meta::<u64>(), None);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Walk {
+        at: Timestamp,
+        left: u32,
+    }
+
+    impl Event<u32> for Walk {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<u32, Walk>) {
+            // Only every fifth phase transition changes the visible data.
+            if self.left.is_multiple_of(5) {
+                *state.state_mut() += 1;
+            }
+            if self.left > 0 {
+                state.schedule(Walk {
+                    at: self.at + 1.0,
+                    left: self.left - 1,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn skip_unchanged_snapshots_keeps_only_changes() {
+        let mut engine = Engine::new(0);
+        engine.set_skip_unchanged_snapshots(true);
+        engine.schedule(Walk { at: 1.0, left: 20 });
+        engine.run_until(30.0);
+        assert_eq!(engine.events_processed(), 21);
+        let series: Vec<(Timestamp, u32)> = engine.history().to_vec();
+        assert_eq!(
+            series,
+            vec![
+                (0.0, 0),
+                (1.0, 1),
+                (6.0, 2),
+                (11.0, 3),
+                (16.0, 4),
+                (21.0, 5)
+            ]
+        );
+    }
+
+    #[test]
+    fn schedule_at_overrides_event_time() {
+        let mut engine = Engine::new(Vec::new());
+        engine.set_event_log(true);
+        let event = Keyed {
+            at: 100.0,
+            priority: 0,
+            id: 7,
+        };
+        engine.schedule_at(3.0, event.clone());
+        engine.schedule_at(1.0, event);
+        engine.run_until(5.0);
+        assert_eq!(*engine.state(), vec![7, 7]);
+        let fired: Vec<Timestamp> = engine.events().iter().map(|&(at, _)| at).collect();
+        assert_eq!(fired, vec![1.0, 3.0]);
+    }
+
+    #[test]
+    fn same_time_events_fire_in_insertion_order() {
+        let mut engine = Engine::new(Vec::new());
+        for id in [10, 11, 12] {
+            engine.schedule(Keyed {
+                at: 2.0,
+                priority: 0,
+                id,
+            });
+        }
+        engine.run_until(2.0);
+        assert_eq!(*engine.state(), vec![10, 11, 12]);
+    }
+
+    #[test]
+    fn step_executes_one_event_at_a_time() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_event_log(true);
+        engine.schedule(Tick { at: 0.0, left: 2 });
+        for expected in [0.0, 0.5, 1.0] {
+            assert_eq!(engine.step(), Some(expected));
+            assert_eq!(engine.now(), expected);
+        }
+        assert_eq!(engine.step(), None);
+        assert_eq!(engine.now(), 1.0);
+        assert_eq!(engine.state().ticks, 3);
+        assert_eq!(engine.history().len(), 4);
+        assert_eq!(engine.events().len(), 3);
+    }
+
+    #[test]
+    fn run_n_events_stops_after_count() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_event_log(true);
+        engine.schedule(Tick {
+            at: 0.0,
+            left: u32::MAX,
+        });
+        assert_eq!(engine.run_n_events(3), 3);
+        assert_eq!(engine.events().len(), 3);
+        assert_eq!(engine.now(), 1.0);
+
+        let mut short = Engine::<Counter, Tick>::new(Counter::default());
+        short.schedule(Tick { at: 0.0, left: 1 });
+        assert_eq!(short.run_n_events(5), 2);
+    }
+
+    #[test]
+    fn run_drains_queue_without_horizon() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.run();
+        assert_eq!(engine.state().ticks, 4);
+        assert_eq!(engine.now(), 1.5);
+        assert_eq!(engine.history().last().unwrap().0, 1.5);
+    }
+
+    #[derive(Clone, Debug, Default)]
+    struct Bakery {
+        flour: u32,
+        bread: u32,
+    }
+
+    /// Flour arrives every 1.0; each batch is baked into two loaves 0.5 later.
+    #[derive(Clone, Debug)]
+    enum Bake {
+        Mill { at: Timestamp },
+        Oven { at: Timestamp },
+    }
+
+    impl Event<Bakery> for Bake {
+        fn time(&self) -> Timestamp {
+            match self {
+                Bake::Mill { at } | Bake::Oven { at } => *at,
+            }
+        }
+        fn execute(self, state: &mut State<Bakery, Bake>) {
+            match self {
+                Bake::Mill { at } => {
+                    state.state_mut().flour += 1;
+                    state.schedule(Bake::Oven { at: at + 0.5 });
+                    state.schedule(Bake::Mill { at: at + 1.0 });
+                }
+                Bake::Oven { .. } => {
+                    let bakery = state.state_mut();
+                    bakery.flour -= 1;
+                    bakery.bread += 2;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn run_until_with_stops_on_state_predicate() {
+        let mut engine = Engine::new(Bakery::default());
+        engine.schedule(Bake::Mill { at: 0.0 });
+        let reason = engine.run_until_with(100.0, |b| b.bread >= 8);
+        assert_eq!(reason, StopReason::Predicate);
+        assert_eq!(engine.state().bread, 8);
+        assert_eq!(engine.now(), 3.5);
+
+        let mut idle = Engine::<Bakery, Bake>::new(Bakery::default());
+        assert_eq!(
+            idle.run_until_with(10.0, |b| b.bread >= 8),
+            StopReason::QueueEmpty
+        );
+    }
+
+    #[test]
+    fn peek_next_time_returns_earliest() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        assert_eq!(engine.peek_next_time(), None);
+        engine.schedule(Tick { at: 3.0, left: 0 });
+        engine.schedule(Tick { at: 1.0, left: 0 });
+        assert_eq!(engine.peek_next_time(), Some(1.0));
+        engine.step();
+        assert_eq!(engine.peek_next_time(), Some(3.0));
+    }
+
+    #[test]
+    fn pending_len_tracks_queue() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        assert!(engine.is_empty());
+        engine.schedule(Tick { at: 0.0, left: 1 });
+        engine.schedule(Tick { at: 2.0, left: 0 });
+        engine.schedule(Tick { at: 3.0, left: 0 });
+        assert_eq!(engine.pending_len(), 3);
+        // The first tick reschedules itself once, so the count stays at three.
+        engine.step();
+        assert_eq!(engine.pending_len(), 3);
+        engine.step();
+        assert_eq!(engine.pending_len(), 2);
+        engine.run();
+        assert!(engine.is_empty());
+    }
+
+    #[test]
+    fn disabled_history_records_no_snapshots() {
+        let mut engine = Engine::<Counter, Tick>::builder(Counter::default())
+            .record_history(false)
+            .build();
+        engine.schedule(Tick {
+            at: 0.0,
+            left: 99_999,
+        });
+        engine.run_until(f64::INFINITY);
+        assert_eq!(engine.events_processed(), 100_000);
+        assert!(engine.history().is_empty());
+        assert_eq!(engine.history.capacity(), 0);
+        assert!(!engine.rewind(1));
+        // No other per-event record grows either.
+        assert_eq!(engine.history_points.capacity(), 0);
+        assert_eq!(engine.causal.capacity(), 0);
+        assert_eq!(engine.events.capacity(), 0);
+        assert_eq!(engine.typed_events.capacity(), 0);
+    }
+
+    #[test]
+    fn history_capacity_keeps_most_recent_snapshots() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default()).with_history_capacity(3);
+        engine.set_rewindable(true);
+        engine.schedule(Tick { at: 0.0, left: 9 });
+        engine.run();
+        assert_eq!(engine.events_processed(), 10);
+        let ticks: Vec<u32> = engine.history().iter().map(|(_, c)| c.ticks).collect();
+        assert_eq!(ticks, vec![8, 9, 10]);
+        assert!(engine.rewind(2));
+        assert_eq!(engine.history().len(), 1);
+        assert!(!engine.rewind(1));
+
+        let mut none = Engine::<Counter, Tick>::new(Counter::default()).with_history_capacity(0);
+        assert!(none.history().is_empty());
+        none.schedule(Tick { at: 0.0, left: 9 });
+        none.run();
+        assert!(none.history().is_empty());
+    }
+
+    #[test]
+    fn sample_interval_spaces_snapshots() {
+        let times = |engine: &Engine<Counter, Tick>| -> Vec<Timestamp> {
+            engine.history().iter().map(|(now, _)| *now).collect()
+        };
+        let mut engine = Engine::new(Counter::default()).with_sample_interval(1.0);
+        engine.schedule(Tick { at: 0.0, left: 9 });
+        engine.run_until(5.0);
+        assert_eq!(times(&engine), vec![0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert_eq!(engine.history().last().unwrap().1.ticks, 10);
+
+        let mut drained = Engine::new(Counter::default()).with_sample_interval(1.0);
+        drained.schedule(Tick { at: 0.0, left: 9 });
+        drained.run();
+        assert_eq!(times(&drained), vec![0.0, 1.0, 2.0, 3.0, 4.0, 4.5]);
+    }
+
+    #[test]
+    fn typed_log_exposes_event_variants() {
+        let mut engine = Engine::new(Bakery::default());
+        engine.set_typed_log(true);
+        engine.set_event_log(true);
+        engine.set_rewindable(true);
+        engine.schedule(Bake::Mill { at: 0.0 });
+        engine.run_until(1.0);
+        let ovens: Vec<Timestamp> = engine
+            .events_typed()
+            .iter()
+            .filter_map(|(_, event)| match event {
+                Bake::Oven { at } => Some(*at),
+                Bake::Mill { .. } => None,
+            })
+            .collect();
+        assert_eq!(ovens, vec![0.5]);
+        assert_eq!(engine.events_typed().len(), engine.events().len());
+
+        assert!(engine.rewind(1));
+        assert_eq!(engine.events_typed().len(), 2);
+        engine.set_typed_log(false);
+        assert!(engine.events_typed().is_empty());
+    }
+
+    /// Events need only be `Clone` for the engine; `Debug` is for the string log.
+    #[derive(Clone)]
+    struct Opaque {
+        at: Timestamp,
+    }
+
+    impl Event<u32> for Opaque {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<u32, Opaque>) {
+            *state.state_mut() += 1;
+        }
+    }
+
+    #[test]
+    fn engine_runs_events_without_debug() {
+        let mut engine = Engine::new(0);
+        engine.schedule(Opaque { at: 1.0 });
+        engine.schedule(Opaque { at: 2.0 });
+        engine.run_until(5.0);
+        assert_eq!(*engine.state(), 2);
+        assert_eq!(engine.history().len(), 4);
+    }
+
+    #[test]
+    fn event_log_covers_events_since_enabled() {
+        let mut engine = Engine::<Counter, Tick>::new(Counter::default());
+        engine.set_rewindable(true);
+        engine.schedule(Tick { at: 0.0, left: 3 });
+        engine.step();
+        assert!(engine.events().is_empty());
+        engine.set_event_log(true);
+        engine.run();
+        assert_eq!(engine.events().len(), 3);
+        assert!(engine.events()[0].1.starts_with("Tick"));
+        assert!(engine.rewind(1));
+        assert_eq!(engine.events().len(), 2);
+    }
+
+    /// User state that owns a resource and cannot be cloned.
+    struct Ledger {
+        entries: Vec<Box<dyn Fn() -> u32>>,
+    }
+
+    struct Record {
+        at: Timestamp,
+    }
+
+    impl Event<Ledger> for Record {
+        fn time(&self) -> Timestamp {
+            self.at
+        }
+        fn execute(self, state: &mut State<Ledger, Record>) {
+            state.state_mut().entries.push(Box::new(|| 1));
+            if self.at < 3.0 {
+                state.schedule(Record { at: self.at + 1.0 });
+            }
+        }
+    }
+
+    #[test]
+    fn engine_without_history_needs_no_clone() {
+        let mut engine = Engine::without_history(Ledger {
+            entries: Vec::new(),
+        });
+        engine.schedule(Record { at: 0.0 });
+        engine.run_until(10.0);
+        assert_eq!(engine.state().entries.len(), 4);
+        assert_eq!(engine.state().entries.iter().map(|f| f()).sum::<u32>(), 4);
+        assert!(engine.history().is_empty());
+        assert!(!engine.rewind(1));
+    }
+
+    /// A clock-cycle counter driven by integer time.
+    #[derive(Clone, Debug)]
+    struct Cycle {
+        at: u64,
+        left: u32,
+    }
+
+    impl Event<Vec<u64>, u64> for Cycle {
+        fn time(&self) -> u64 {
+            self.at
+        }
+        fn execute(self, state: &mut State<Vec<u64>, Cycle, u64>) {
+            let now = state.now();
+            state.state_mut().push(now);
+            if self.left > 0 {
+                state.schedule(Cycle {
+                    at: now + 3,
+                    left: self.left - 1,
+                });
+            }
+        }
+    }
+
+    #[test]
+    fn integer_time_advances_in_exact_ticks() {
+        let mut engine = Engine::<Vec<u64>, Cycle, u64>::new(Vec::new());
+        // 2^53 + 1 is not representable as `f64`, so float time would round it.
+        engine.schedule(Cycle {
+            at: 9_007_199_254_740_993,
+            left: 3,
+        });
+        engine.run_until(u64::MAX - 1);
+        assert_eq!(
+            *engine.state(),
+            vec![
+                9_007_199_254_740_993,
+                9_007_199_254_740_996,
+                9_007_199_254_740_999,
+                9_007_199_254_741_002
+            ]
+        );
+        assert_eq!(engine.now(), u64::MAX - 1);
+
+        let mut stepped = Engine::<Vec<u64>, Cycle, u64>::new(Vec::new());
+        stepped.schedule(Cycle { at: 2, left: 1 });
+        stepped.run();
+        assert_eq!(stepped.now(), 5);
+    }
+
+    #[test]
+    fn horizon_epsilon_admits_drifted_events() {
+        let drifted = Tick {
+            at: 10.0 + 1e-9,
+            left: 0,
+        };
+
+        let mut strict = Engine::<Counter, Tick>::new(Counter::default());
+        strict.schedule(drifted.clone());
+        strict.run_until(10.0);
+        assert_eq!(strict.state().ticks, 0);
+
+        let mut tolerant = Engine::<Counter, Tick>::new(Counter::default());
+        tolerant.set_horizon_epsilon(1e-6);
+        tolerant.schedule(drifted);
+        tolerant.run_until(10.0);
+        assert_eq!(tolerant.state().ticks, 1);
     }
 }
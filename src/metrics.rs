@@ -0,0 +1,35 @@
+//! Named scalar metrics carried alongside the user state.
+
+use std::collections::BTreeMap;
+
+/// A small set of named `f64` values, e.g. counters or gauges for exploratory models.
+///
+/// Metrics live in `State`, so events update them through `State::metrics_mut` and
+/// `Engine::rewind` restores their earlier values. Every history snapshot keeps a copy,
+/// readable through `Engine::history_metrics`. Unset names read as `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metrics {
+    values: BTreeMap<&'static str, f64>,
+}
+
+impl Metrics {
+    /// Set `name` to `value`, replacing any previous value.
+    pub fn set(&mut self, name: &'static str, value: f64) {
+        self.values.insert(name, value);
+    }
+
+    /// Add `delta` to `name`, treating an unset metric as 0.
+    pub fn add(&mut self, name: &'static str, delta: f64) {
+        *self.values.entry(name).or_insert(0.0) += delta;
+    }
+
+    /// Current value of `name`, if it was ever set.
+    pub fn get(&self, name: &str) -> Option<f64> {
+        self.values.get(name).copied()
+    }
+
+    /// Iterate over all metrics in name order.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, f64)> + '_ {
+        self.values.iter().map(|(name, value)| (*name, *value))
+    }
+}
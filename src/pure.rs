@@ -0,0 +1,140 @@
+//! Events written as pure functions of the user data.
+
+use crate::{Event, SimTime, State, Timestamp};
+
+/// An event whose handler returns the events it schedules instead of pushing them
+/// through a `State` handle.
+///
+/// The handler sees only the current time and the user data, so it can be unit-tested
+/// by calling it directly. Wrap such events in `Pure` to use them where an `Event` is
+/// expected:
+///
+/// ```
+/// use dessert::{Engine, Pure, PureEvent};
+///
+/// struct Countdown(f64, u32);
+///
+/// impl PureEvent<Vec<u32>> for Countdown {
+///     fn time(&self) -> f64 {
+///         self.0
+///     }
+///     fn execute(self, now: f64, fired: &mut Vec<u32>) -> Vec<Self> {
+///         fired.push(self.1);
+///         match self.1 {
+///             0 => vec![],
+///             n => vec![Countdown(now + 1.0, n - 1)],
+///         }
+///     }
+/// }
+///
+/// let mut engine = Engine::new(Vec::new());
+/// engine.schedule(Pure(Countdown(0.0, 2)));
+/// engine.run_until(10.0);
+/// assert_eq!(engine.state(), &vec![2, 1, 0]);
+/// ```
+pub trait PureEvent<S, T: SimTime = Timestamp>: Sized {
+    /// Time at which this event should fire (see `Event::time`).
+    fn time(&self) -> T;
+
+    /// Short, static name of this kind of event (see `Event::label`).
+    fn label(&self) -> &'static str {
+        "event"
+    }
+
+    /// Tie-break among events at the same time (see `Event::priority`).
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Phase of this event within an instant (see `Event::phase`).
+    fn phase(&self) -> u8 {
+        0
+    }
+
+    /// Update `data` at time `now` and return the events to schedule, each at its own
+    /// `time()`.
+    fn execute(self, now: T, data: &mut S) -> Vec<Self>;
+}
+
+/// Adapter running a `PureEvent` as an `Event`: the returned events are scheduled
+/// as if passed to `State::schedule_all`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Pure<E>(pub E);
+
+impl<S, E: PureEvent<S, T>, T: SimTime> Event<S, T> for Pure<E> {
+    fn time(&self) -> T {
+        self.0.time()
+    }
+
+    fn label(&self) -> &'static str {
+        self.0.label()
+    }
+
+    fn priority(&self) -> i32 {
+        self.0.priority()
+    }
+
+    fn phase(&self) -> u8 {
+        self.0.phase()
+    }
+
+    fn execute(self, state: &mut State<S, Self, T>) {
+        let now = state.now();
+        let next = self.0.execute(now, state.state_mut());
+        state.schedule_all(next.into_iter().map(Pure));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Engine;
+
+    #[derive(Clone, Debug, PartialEq)]
+    enum Bakery {
+        /// Dough goes in; the bread is ready after `bake` time units.
+        Load {
+            at: Timestamp,
+            bake: Timestamp,
+        },
+        Unload {
+            at: Timestamp,
+        },
+    }
+
+    impl PureEvent<u32> for Bakery {
+        fn time(&self) -> Timestamp {
+            match *self {
+                Bakery::Load { at, .. } | Bakery::Unload { at } => at,
+            }
+        }
+        fn execute(self, now: Timestamp, loaves: &mut u32) -> Vec<Self> {
+            match self {
+                Bakery::Load { bake, .. } => vec![Bakery::Unload { at: now + bake }],
+                Bakery::Unload { .. } => {
+                    *loaves += 1;
+                    Vec::new()
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn handler_returns_the_events_it_schedules() {
+        let mut loaves = 0;
+        let next = Bakery::Load { at: 1.0, bake: 2.5 }.execute(1.0, &mut loaves);
+        assert_eq!(next, vec![Bakery::Unload { at: 3.5 }]);
+        assert_eq!(loaves, 0);
+        assert!(Bakery::Unload { at: 3.5 }
+            .execute(3.5, &mut loaves)
+            .is_empty());
+        assert_eq!(loaves, 1);
+
+        let mut engine = Engine::new(0);
+        engine.schedule(Pure(Bakery::Load { at: 1.0, bake: 2.5 }));
+        engine.run_until(3.0);
+        assert_eq!(*engine.state(), 0);
+        engine.run_until(4.0);
+        assert_eq!(*engine.state(), 1);
+    }
+}
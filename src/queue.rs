@@ -0,0 +1,373 @@
+//! Pending-event queues.
+//!
+//! The engine keeps its pending events in an `EventQueue`. The default is a binary heap
+//! (O(log n) per operation); a calendar queue (amortized O(1) for the near-uniform
+//! timestamp spreads typical of discrete-event models) can be chosen with
+//! `EngineBuilder::queue`. Both pop events in `ScheduleKey` order, so the choice never
+//! changes the outcome of a run.
+
+use std::collections::BinaryHeap;
+
+use crate::{Scheduled, SimTime};
+
+/// Which data structure holds the pending events (see `EngineBuilder::queue`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueueKind {
+    /// A binary heap: O(log n) push and pop whatever the time distribution.
+    #[default]
+    BinaryHeap,
+    /// A calendar queue: events are bucketed by time, giving amortized O(1) push and
+    /// pop when pending times are spread fairly evenly.
+    Calendar,
+}
+
+/// Operations the engine needs from its pending-event store.
+pub(crate) trait EventQueue<S, E, T: SimTime> {
+    fn push(&mut self, item: Scheduled<S, E, T>);
+    /// Remove the entry with the smallest `ScheduleKey`.
+    fn pop_min(&mut self) -> Option<Scheduled<S, E, T>>;
+    fn peek_min(&self) -> Option<&Scheduled<S, E, T>>;
+    fn len(&self) -> usize;
+    /// All entries, in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = &Scheduled<S, E, T>> + '_>;
+    /// Remove all entries, in no particular order.
+    fn drain(&mut self) -> Vec<Scheduled<S, E, T>>;
+    /// Make room for at least `additional` more entries, where the structure allows it.
+    fn reserve(&mut self, additional: usize);
+    /// Remove all entries, keeping the allocated storage.
+    fn clear(&mut self);
+    /// Remove and return the first entry found matching `pred`, in O(n).
+    fn remove(&mut self, pred: &dyn Fn(&Scheduled<S, E, T>) -> bool) -> Option<Scheduled<S, E, T>>;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<S, E, T: SimTime> EventQueue<S, E, T> for BinaryHeap<Scheduled<S, E, T>> {
+    fn push(&mut self, item: Scheduled<S, E, T>) {
+        BinaryHeap::push(self, item);
+    }
+
+    fn pop_min(&mut self) -> Option<Scheduled<S, E, T>> {
+        // `Scheduled` orders latest-first, so the heap's maximum is the earliest entry.
+        self.pop()
+    }
+
+    fn peek_min(&self) -> Option<&Scheduled<S, E, T>> {
+        self.peek()
+    }
+
+    fn len(&self) -> usize {
+        BinaryHeap::len(self)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Scheduled<S, E, T>> + '_> {
+        Box::new(BinaryHeap::iter(self))
+    }
+
+    fn drain(&mut self) -> Vec<Scheduled<S, E, T>> {
+        std::mem::take(self).into_vec()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        BinaryHeap::reserve(self, additional);
+    }
+
+    fn clear(&mut self) {
+        BinaryHeap::clear(self);
+    }
+
+    fn remove(&mut self, pred: &dyn Fn(&Scheduled<S, E, T>) -> bool) -> Option<Scheduled<S, E, T>> {
+        // No decrease-key: take the entries out and heapify the rest again.
+        let mut items = std::mem::take(self).into_vec();
+        let found = items.iter().position(pred).map(|at| items.swap_remove(at));
+        *self = BinaryHeap::from(items);
+        found
+    }
+}
+
+/// Fewest buckets a calendar queue shrinks to.
+const MIN_BUCKETS: usize = 16;
+
+/// Number of earliest events sampled to pick the bucket width on resize.
+const WIDTH_SAMPLE: usize = 25;
+
+/// A calendar queue (Brown, 1988): a ring of buckets each covering `width` time units.
+///
+/// An event at time `t` lives in "day" `floor(t / width)`, stored in bucket
+/// `day mod buckets.len()`. Popping scans the ring day by day from `current`, so when
+/// the width matches the typical gap between events each pop inspects O(1) buckets.
+/// The ring is resized, and the width re-estimated, when the population doubles or
+/// halves.
+#[derive(Clone)]
+pub(crate) struct CalendarQueue<S, E, T: SimTime> {
+    /// Each bucket is sorted latest-first (ascending `Scheduled` order), so its
+    /// earliest entry is last.
+    buckets: Vec<Vec<Scheduled<S, E, T>>>,
+    width: f64,
+    /// Day the scan is at; no pending event lies on an earlier day.
+    current: i64,
+    len: usize,
+}
+
+impl<S, E, T: SimTime> CalendarQueue<S, E, T> {
+    pub(crate) fn new() -> Self {
+        Self {
+            buckets: (0..MIN_BUCKETS).map(|_| Vec::new()).collect(),
+            width: 1.0,
+            current: 0,
+            len: 0,
+        }
+    }
+
+    fn day(&self, item: &Scheduled<S, E, T>) -> i64 {
+        (item.at.to_f64() / self.width).floor() as i64
+    }
+
+    fn bucket(&self, day: i64) -> usize {
+        day.rem_euclid(self.buckets.len() as i64) as usize
+    }
+
+    fn insert(&mut self, item: Scheduled<S, E, T>) {
+        let day = self.day(&item);
+        if self.len == 0 || day < self.current {
+            self.current = day;
+        }
+        let bucket = self.bucket(day);
+        let entries = &mut self.buckets[bucket];
+        let at = entries.partition_point(|entry| *entry < item);
+        entries.insert(at, item);
+        self.len += 1;
+    }
+
+    /// Bucket holding the earliest entry, and that entry's day.
+    fn find_min(&self) -> Option<(usize, i64)> {
+        if self.len == 0 {
+            return None;
+        }
+        for day in self.current..self.current.saturating_add(self.buckets.len() as i64) {
+            let bucket = self.bucket(day);
+            if let Some(first) = self.buckets[bucket].last() {
+                if self.day(first) == day {
+                    return Some((bucket, day));
+                }
+            }
+        }
+        // A full lap found nothing due: the events are sparse, so search directly.
+        let (bucket, first) = self
+            .buckets
+            .iter()
+            .enumerate()
+            .filter_map(|(bucket, entries)| Some((bucket, entries.last()?)))
+            .max_by(|a, b| a.1.cmp(b.1))?;
+        Some((bucket, self.day(first)))
+    }
+
+    /// Rebuild with `count` buckets, re-estimating the width from the earliest events.
+    fn resize(&mut self, count: usize) {
+        let items: Vec<_> = self.buckets.iter_mut().flat_map(std::mem::take).collect();
+        let mut times: Vec<f64> = items.iter().map(|item| item.at.to_f64()).collect();
+        let sample = times.len().min(WIDTH_SAMPLE);
+        if sample >= 2 {
+            if sample < times.len() {
+                times.select_nth_unstable_by(sample - 1, f64::total_cmp);
+                times.truncate(sample);
+            }
+            times.sort_unstable_by(f64::total_cmp);
+            let gap = (times[sample - 1] - times[0]) / (sample - 1) as f64;
+            if gap > 0.0 && gap.is_finite() {
+                self.width = 3.0 * gap;
+            }
+        }
+        self.buckets = (0..count).map(|_| Vec::new()).collect();
+        self.len = 0;
+        for item in items {
+            self.insert(item);
+        }
+    }
+}
+
+impl<S, E, T: SimTime> EventQueue<S, E, T> for CalendarQueue<S, E, T> {
+    fn push(&mut self, item: Scheduled<S, E, T>) {
+        self.insert(item);
+        if self.len > 2 * self.buckets.len() {
+            self.resize(2 * self.buckets.len());
+        }
+    }
+
+    fn pop_min(&mut self) -> Option<Scheduled<S, E, T>> {
+        let (bucket, day) = self.find_min()?;
+        self.current = day;
+        let item = self.buckets[bucket].pop()?;
+        self.len -= 1;
+        if self.buckets.len() > MIN_BUCKETS && self.len < self.buckets.len() / 2 {
+            self.resize(self.buckets.len() / 2);
+        }
+        Some(item)
+    }
+
+    fn peek_min(&self) -> Option<&Scheduled<S, E, T>> {
+        let (bucket, _) = self.find_min()?;
+        self.buckets[bucket].last()
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Scheduled<S, E, T>> + '_> {
+        Box::new(self.buckets.iter().flatten())
+    }
+
+    fn drain(&mut self) -> Vec<Scheduled<S, E, T>> {
+        self.len = 0;
+        self.buckets.iter_mut().flat_map(std::mem::take).collect()
+    }
+
+    fn reserve(&mut self, _additional: usize) {
+        // Buckets are sized by the times of the events they receive, which are not
+        // known yet; growing the ring early would only spread them thinner.
+    }
+
+    fn clear(&mut self) {
+        for bucket in &mut self.buckets {
+            bucket.clear();
+        }
+        self.current = 0;
+        self.len = 0;
+    }
+
+    fn remove(&mut self, pred: &dyn Fn(&Scheduled<S, E, T>) -> bool) -> Option<Scheduled<S, E, T>> {
+        let found = self.buckets.iter_mut().find_map(|bucket| {
+            let at = bucket.iter().position(pred)?;
+            Some(bucket.remove(at))
+        });
+        if found.is_some() {
+            self.len -= 1;
+        }
+        found
+    }
+}
+
+/// The queue an engine was built with.
+#[derive(Clone)]
+pub(crate) enum Queue<S, E, T: SimTime> {
+    Heap(BinaryHeap<Scheduled<S, E, T>>),
+    Calendar(CalendarQueue<S, E, T>),
+}
+
+impl<S, E, T: SimTime> Queue<S, E, T> {
+    pub(crate) fn new(kind: QueueKind) -> Self {
+        match kind {
+            QueueKind::BinaryHeap => Self::Heap(BinaryHeap::new()),
+            QueueKind::Calendar => Self::Calendar(CalendarQueue::new()),
+        }
+    }
+
+    /// An empty queue of `kind` with room for `capacity` entries, where the
+    /// structure allows it.
+    pub(crate) fn with_capacity(kind: QueueKind, capacity: usize) -> Self {
+        let mut queue = Self::new(kind);
+        queue.reserve(capacity);
+        queue
+    }
+
+    fn inner(&self) -> &dyn EventQueue<S, E, T> {
+        match self {
+            Self::Heap(heap) => heap,
+            Self::Calendar(calendar) => calendar,
+        }
+    }
+
+    fn inner_mut(&mut self) -> &mut dyn EventQueue<S, E, T> {
+        match self {
+            Self::Heap(heap) => heap,
+            Self::Calendar(calendar) => calendar,
+        }
+    }
+}
+
+impl<S, E, T: SimTime> EventQueue<S, E, T> for Queue<S, E, T> {
+    fn push(&mut self, item: Scheduled<S, E, T>) {
+        self.inner_mut().push(item);
+    }
+
+    fn pop_min(&mut self) -> Option<Scheduled<S, E, T>> {
+        self.inner_mut().pop_min()
+    }
+
+    fn peek_min(&self) -> Option<&Scheduled<S, E, T>> {
+        self.inner().peek_min()
+    }
+
+    fn len(&self) -> usize {
+        self.inner().len()
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = &Scheduled<S, E, T>> + '_> {
+        self.inner().iter()
+    }
+
+    fn drain(&mut self) -> Vec<Scheduled<S, E, T>> {
+        self.inner_mut().drain()
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        self.inner_mut().reserve(additional);
+    }
+
+    fn clear(&mut self) {
+        self.inner_mut().clear();
+    }
+
+    fn remove(&mut self, pred: &dyn Fn(&Scheduled<S, E, T>) -> bool) -> Option<Scheduled<S, E, T>> {
+        self.inner_mut().remove(pred)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, State, Timestamp};
+
+    struct Mark;
+
+    impl Event<()> for Mark {
+        fn time(&self) -> Timestamp {
+            0.0
+        }
+        fn execute(self, _: &mut State<(), Mark>) {}
+    }
+
+    fn pop_order(queue: &mut Queue<(), Mark, Timestamp>, times: &[Timestamp]) -> Vec<u64> {
+        for (seq, &at) in times.iter().enumerate() {
+            queue.push(Scheduled::new(at, 0, seq as u64, None, Mark));
+        }
+        let mut order = Vec::new();
+        // Interleave pushes with pops so the calendar scan position moves back too.
+        while let Some(item) = queue.pop_min() {
+            order.push(item.seq);
+            if item.seq % 7 == 0 && order.len() < times.len() {
+                let seq = times.len() as u64 + item.seq;
+                queue.push(Scheduled::new(item.at, 0, seq, None, Mark));
+            }
+        }
+        order
+    }
+
+    #[test]
+    fn calendar_pops_in_the_same_order_as_the_heap() {
+        // Many ties and a few far-off outliers, which exercise the sparse search.
+        let times: Vec<Timestamp> = (0..2_000u64)
+            .map(|i| match i % 50 {
+                0 => 1e6 + i as f64,
+                _ => ((i * 7919) % 97) as f64 * 0.25,
+            })
+            .collect();
+        let heap = pop_order(&mut Queue::new(QueueKind::BinaryHeap), &times);
+        let calendar = pop_order(&mut Queue::new(QueueKind::Calendar), &times);
+        assert_eq!(heap.len(), calendar.len());
+        assert_eq!(heap, calendar);
+    }
+}
@@ -0,0 +1,178 @@
+//! Bounded servers with a FIFO wait queue.
+//!
+//! A `Resource` models a pool of identical units, e.g. the workers of a mill. Events
+//! request a unit with `State::acquire`, passing the event to run once it is granted;
+//! when every unit is busy the request waits in line and is resumed by a later
+//! `State::release`.
+
+use std::collections::VecDeque;
+
+use crate::{Engine, Event, SimTime, State};
+
+/// Identifies a resource registered with `State::add_resource`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResourceId(usize);
+
+/// A pool of `capacity` units and the requests waiting for one, in arrival order.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Resource<E> {
+    capacity: u32,
+    in_use: u32,
+    waiting: VecDeque<E>,
+}
+
+impl<E> Resource<E> {
+    /// Total number of units.
+    pub fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Units currently held.
+    pub fn in_use(&self) -> u32 {
+        self.in_use
+    }
+
+    /// Units free right now.
+    pub fn available(&self) -> u32 {
+        self.capacity - self.in_use
+    }
+
+    /// Number of requests waiting for a unit.
+    pub fn waiting(&self) -> usize {
+        self.waiting.len()
+    }
+
+    /// Free every unit and drop the waiting requests.
+    pub(crate) fn reset(&mut self) {
+        self.in_use = 0;
+        self.waiting.clear();
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> State<S, E, T, C> {
+    /// Register a resource with `capacity` units, all initially free.
+    pub fn add_resource(&mut self, capacity: u32) -> ResourceId {
+        self.resources.push(Resource {
+            capacity,
+            in_use: 0,
+            waiting: VecDeque::new(),
+        });
+        ResourceId(self.resources.len() - 1)
+    }
+
+    /// The resource registered as `id`.
+    pub fn resource(&self, id: ResourceId) -> &Resource<E> {
+        &self.resources[id.0]
+    }
+
+    /// Request a unit of `id` and run `then` once it is granted.
+    ///
+    /// If a unit is free it is taken and `then` is scheduled at `now`; otherwise the
+    /// request waits behind earlier ones until `release` hands it a unit. Returns whether
+    /// the unit was granted immediately. Whoever runs `then` holds the unit and must
+    /// eventually `release` it.
+    pub fn acquire(&mut self, id: ResourceId, then: E) -> bool {
+        let resource = &mut self.resources[id.0];
+        if resource.in_use < resource.capacity {
+            resource.in_use += 1;
+            self.schedule_at(self.now, then);
+            true
+        } else {
+            resource.waiting.push_back(then);
+            false
+        }
+    }
+
+    /// Return a unit of `id`. If requests are waiting, the unit passes straight to the
+    /// oldest one, whose event is scheduled at `now`.
+    ///
+    /// # Panics
+    ///
+    /// If no unit of `id` is in use.
+    pub fn release(&mut self, id: ResourceId) {
+        let resource = &mut self.resources[id.0];
+        assert!(resource.in_use > 0, "released a unit of an idle resource");
+        match resource.waiting.pop_front() {
+            Some(next) => self.schedule_at(self.now, next),
+            None => resource.in_use -= 1,
+        }
+    }
+}
+
+impl<S, E: Event<S, T, C>, T: SimTime, C> Engine<S, E, T, C> {
+    /// External counterpart of `State::add_resource`, for setting up a model.
+    pub fn add_resource(&mut self, capacity: u32) -> ResourceId {
+        self.state.add_resource(capacity)
+    }
+
+    /// External counterpart of `State::resource`.
+    pub fn resource(&self, id: ResourceId) -> &Resource<E> {
+        self.state.resource(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Timestamp;
+
+    #[derive(Clone, Debug, Default)]
+    struct Shop {
+        server: Option<ResourceId>,
+        /// (customer, service start, departure)
+        served: Vec<(u32, Timestamp, Timestamp)>,
+    }
+
+    #[derive(Clone, Debug)]
+    enum Customer {
+        Arrive { at: Timestamp, id: u32 },
+        Start { id: u32 },
+        Depart { id: u32, started: Timestamp },
+    }
+
+    impl Event<Shop> for Customer {
+        fn time(&self) -> Timestamp {
+            match self {
+                Customer::Arrive { at, .. } => *at,
+                // Scheduled through `acquire`/`schedule_at`, which supply the time.
+                Customer::Start { .. } | Customer::Depart { .. } => 0.0,
+            }
+        }
+        fn execute(self, state: &mut State<Shop, Customer>) {
+            let server = state.state().server.expect("server registered");
+            match self {
+                Customer::Arrive { id, .. } => {
+                    state.acquire(server, Customer::Start { id });
+                }
+                Customer::Start { id } => {
+                    let started = state.now();
+                    state.schedule_at(started + 3.0, Customer::Depart { id, started });
+                }
+                Customer::Depart { id, started } => {
+                    let now = state.now();
+                    state.state_mut().served.push((id, started, now));
+                    state.release(server);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_server_serializes_arrivals() {
+        let mut engine = Engine::new(Shop::default());
+        let server = engine.add_resource(1);
+        engine.state_mut().server = Some(server);
+        engine.schedule(Customer::Arrive { at: 0.0, id: 1 });
+        engine.schedule(Customer::Arrive { at: 1.0, id: 2 });
+
+        engine.run_until(2.0);
+        assert_eq!(engine.resource(server).in_use(), 1);
+        assert_eq!(engine.resource(server).waiting(), 1);
+
+        engine.run_until(10.0);
+        assert_eq!(engine.state().served, vec![(1, 0.0, 3.0), (2, 3.0, 6.0)]);
+        assert_eq!(engine.resource(server).available(), 1);
+        assert_eq!(engine.resource(server).waiting(), 0);
+    }
+}
@@ -0,0 +1,142 @@
+//! The simulation time abstraction.
+
+use std::cmp::Ordering;
+use std::fmt::Debug;
+use std::ops::{Add, AddAssign, Div, Mul, Sub};
+
+/// A type usable as simulation time.
+///
+/// The engine only needs to order times, add delays to them and know where time starts.
+/// `f64` (the default, see `Timestamp`) gives continuous time; `u64` gives exact integer
+/// ticks, e.g. clock cycles, with no rounding.
+pub trait SimTime:
+    Copy + PartialOrd + Add<Output = Self> + Sub<Output = Self> + Debug + 'static
+{
+    /// The time at which engines start by default.
+    const ZERO: Self;
+
+    /// A time no event can exceed, used as the horizon when running without one.
+    const MAX: Self;
+
+    /// Total order used by the event queue.
+    fn cmp_time(&self, other: &Self) -> Ordering;
+
+    /// Whether an event may be scheduled at this time. Always `true` for integers;
+    /// `false` for NaN and infinite floats.
+    fn is_finite(&self) -> bool;
+
+    /// This time (or duration) as `f64`, for time-weighted statistics.
+    fn to_f64(self) -> f64;
+}
+
+impl SimTime for f64 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = f64::INFINITY;
+
+    fn cmp_time(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
+
+    fn is_finite(&self) -> bool {
+        f64::is_finite(*self)
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+impl SimTime for u64 {
+    const ZERO: Self = 0;
+    const MAX: Self = u64::MAX;
+
+    fn cmp_time(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+
+    fn is_finite(&self) -> bool {
+        true
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+/// A span of simulated time in the default `f64` time base, as opposed to a point in
+/// time (`Timestamp`).
+///
+/// Keeping delays in their own type turns a swapped "when" and "how long" into a type
+/// error: a `Duration` can be added to a `Timestamp`, but two timestamps cannot be
+/// passed where a delay is expected (see `State::schedule_after`). Bare `f64` times
+/// keep working everywhere else.
+#[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd)]
+pub struct Duration(pub f64);
+
+impl From<f64> for Duration {
+    fn from(units: f64) -> Self {
+        Self(units)
+    }
+}
+
+impl From<Duration> for f64 {
+    fn from(duration: Duration) -> Self {
+        duration.0
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, rhs: Duration) -> Duration {
+        Duration(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, rhs: Duration) -> Duration {
+        Duration(self.0 - rhs.0)
+    }
+}
+
+impl Mul<f64> for Duration {
+    type Output = Duration;
+
+    fn mul(self, factor: f64) -> Duration {
+        Duration(self.0 * factor)
+    }
+}
+
+impl Div<f64> for Duration {
+    type Output = Duration;
+
+    fn div(self, divisor: f64) -> Duration {
+        Duration(self.0 / divisor)
+    }
+}
+
+/// `Timestamp + Duration` is a later `Timestamp`.
+impl Add<Duration> for f64 {
+    type Output = f64;
+
+    fn add(self, rhs: Duration) -> f64 {
+        self + rhs.0
+    }
+}
+
+impl AddAssign<Duration> for f64 {
+    fn add_assign(&mut self, rhs: Duration) {
+        *self += rhs.0;
+    }
+}
+
+/// `Timestamp - Duration` is an earlier `Timestamp`.
+impl Sub<Duration> for f64 {
+    type Output = f64;
+
+    fn sub(self, rhs: Duration) -> f64 {
+        self - rhs.0
+    }
+}